@@ -0,0 +1,337 @@
+use std::fmt;
+use serde::Serialize;
+
+use crate::*;
+use crate::rolls;
+
+// Actual pieces instead of mainstat sums, so a build is something that can
+// exist in game: one piece per slot, each with a mainstat its slot can have
+// and substats that aren't its mainstat.
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub enum Slot {
+	Flower,
+	Plume,
+	Sands,
+	Goblet,
+	Circlet,
+}
+
+impl Slot {
+	pub const ALL: [Slot; 5] = [Slot::Flower, Slot::Plume, Slot::Sands, Slot::Goblet, Slot::Circlet];
+
+	// Every mainstat the slot can come with
+	pub fn main_stats(&self) -> &'static [ArtifactStat] {
+		use ArtifactStat::*;
+		match self {
+			Slot::Flower => &[Hp],
+			Slot::Plume => &[Atk],
+			Slot::Sands => &[HpPercent, AtkPercent, DefPercent, Em, EnergyRecharge],
+			Slot::Goblet => &[HpPercent, AtkPercent, DefPercent, Em, ElementalDmg],
+			Slot::Circlet => &[HpPercent, AtkPercent, DefPercent, Em, CritRate, CritDamage, Healing],
+		}
+	}
+
+	pub fn name(&self) -> &'static str {
+		match self {
+			Slot::Flower => "flower",
+			Slot::Plume => "plume",
+			Slot::Sands => "sands",
+			Slot::Goblet => "goblet",
+			Slot::Circlet => "circlet",
+		}
+	}
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize)]
+pub enum ArtifactStat {
+	Hp,
+	Atk,
+	Def,
+	HpPercent,
+	AtkPercent,
+	DefPercent,
+	Em,
+	EnergyRecharge,
+	// Of the character's own element, physical goblets aren't modelled
+	ElementalDmg,
+	Healing,
+	CritRate,
+	CritDamage,
+}
+
+impl ArtifactStat {
+	// Stats a piece can roll into, whatever its mainstat
+	pub const SUBSTATS: [ArtifactStat; 10] = [
+		ArtifactStat::Hp,
+		ArtifactStat::Atk,
+		ArtifactStat::Def,
+		ArtifactStat::HpPercent,
+		ArtifactStat::AtkPercent,
+		ArtifactStat::DefPercent,
+		ArtifactStat::Em,
+		ArtifactStat::EnergyRecharge,
+		ArtifactStat::CritRate,
+		ArtifactStat::CritDamage,
+	];
+
+	// Mainstat of a level 20 5* piece
+	pub fn main_value(&self) -> f32 {
+		match self {
+			ArtifactStat::Hp => 4780.0,
+			ArtifactStat::Atk => 311.0,
+			// Only ever a substat
+			ArtifactStat::Def => 0.0,
+			ArtifactStat::HpPercent | ArtifactStat::AtkPercent | ArtifactStat::ElementalDmg => 46.6,
+			ArtifactStat::DefPercent => 58.3,
+			ArtifactStat::Em => 187.0,
+			ArtifactStat::EnergyRecharge => 51.8,
+			ArtifactStat::Healing => 35.9,
+			ArtifactStat::CritRate => 31.1,
+			ArtifactStat::CritDamage => 62.2,
+		}
+	}
+
+	// Where it goes in `Mainstats`. Flat HP and ATK are on every build, the
+	// stats add them on their own.
+	pub fn mainstat_index(&self) -> Option<usize> {
+		match self {
+			ArtifactStat::Em => Some(0),
+			ArtifactStat::HpPercent => Some(1),
+			ArtifactStat::AtkPercent => Some(2),
+			ArtifactStat::ElementalDmg => Some(3),
+			ArtifactStat::CritRate => Some(4),
+			ArtifactStat::CritDamage => Some(5),
+			ArtifactStat::DefPercent => Some(6),
+			ArtifactStat::EnergyRecharge => Some(7),
+			_ => None
+		}
+	}
+
+	// Where it goes in `Substats`, there's no room for flat rolls there
+	pub fn substat_index(&self) -> Option<usize> {
+		match self {
+			ArtifactStat::HpPercent => Some(0),
+			ArtifactStat::AtkPercent => Some(1),
+			ArtifactStat::Em => Some(2),
+			ArtifactStat::CritRate => Some(3),
+			ArtifactStat::CritDamage => Some(4),
+			ArtifactStat::DefPercent => Some(5),
+			ArtifactStat::EnergyRecharge => Some(6),
+			_ => None
+		}
+	}
+}
+
+impl fmt::Display for ArtifactStat {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		let label = match self {
+			ArtifactStat::Hp => "HP",
+			ArtifactStat::Atk => "ATK",
+			ArtifactStat::Def => "DEF",
+			ArtifactStat::HpPercent => "HP%",
+			ArtifactStat::AtkPercent => "ATK%",
+			ArtifactStat::DefPercent => "DEF%",
+			ArtifactStat::Em => "EM",
+			ArtifactStat::EnergyRecharge => "ER%",
+			ArtifactStat::ElementalDmg => "BONUS%",
+			ArtifactStat::Healing => "Healing%",
+			ArtifactStat::CritRate => "CR",
+			ArtifactStat::CritDamage => "CD",
+		};
+		write!(f, "{}", label)
+	}
+}
+
+// A level 20 5* piece, see `rolls` for how its substats come about
+#[derive(Clone, Debug, Serialize)]
+pub struct Artifact {
+	pub slot: Slot,
+	// None when it doesn't matter, i.e. the layouts the search goes through
+	pub set: Option<String>,
+	pub main_stat: ArtifactStat,
+	// Number of rolls into each substat
+	pub substats: Vec<(ArtifactStat, usize)>,
+}
+
+impl Artifact {
+	pub fn new(slot: Slot, set: Option<&str>, main_stat: ArtifactStat, substats: Vec<(ArtifactStat, usize)>) -> Result<Self, String> {
+		if !slot.main_stats().contains(&main_stat) {
+			return Err(format!("a {} can't have {} as its mainstat", slot.name(), main_stat));
+		}
+		if substats.len() > rolls::STARTING_SUBSTATS {
+			return Err(format!("a {} has {} substats, pieces only get {}", slot.name(), substats.len(), rolls::STARTING_SUBSTATS));
+		}
+		for (i, (stat, rolls)) in substats.iter().enumerate() {
+			if !ArtifactStat::SUBSTATS.contains(stat) {
+				return Err(format!("{} can't be a substat", stat));
+			}
+			if *stat == main_stat {
+				return Err(format!("a {} with {} as its mainstat can't roll it as a substat", slot.name(), stat));
+			}
+			if substats[..i].iter().any(|x| x.0 == *stat) {
+				return Err(format!("a {} has {} as a substat twice", slot.name(), stat));
+			}
+			if *rolls == 0 {
+				return Err(format!("a {} has {} as a substat without any rolls", slot.name(), stat));
+			}
+			if *rolls > rolls::ROLLS_PER_SUBSTAT {
+				return Err(format!("a {} has {} rolls into {}, a substat only gets {}", slot.name(), rolls, stat, rolls::ROLLS_PER_SUBSTAT));
+			}
+		}
+		// Each substat it starts with is one roll, the upgrades add the rest
+		let total: usize = substats.iter().map(|x| x.1).sum();
+		if total > rolls::UPGRADES + substats.len() {
+			return Err(format!(
+				"a {} has {} rolls into {} substats, it only gets {} into them",
+				slot.name(), total, substats.len(), rolls::UPGRADES + substats.len()
+			));
+		}
+		Ok(Artifact { slot, set: set.map(|x| x.to_string()), main_stat, substats })
+	}
+
+	// Just the mainstat, for layouts
+	pub fn bare(slot: Slot, main_stat: ArtifactStat) -> Result<Self, String> {
+		Artifact::new(slot, None, main_stat, Vec::new())
+	}
+}
+
+// Five pieces, one per slot, kept in slot order
+#[derive(Clone, Debug, Serialize)]
+pub struct ArtifactBuild {
+	pieces: Vec<Artifact>,
+}
+
+impl ArtifactBuild {
+	pub fn new(mut pieces: Vec<Artifact>) -> Result<Self, String> {
+		pieces.sort_by_key(|x| x.slot);
+		for slot in Slot::ALL {
+			match pieces.iter().filter(|x| x.slot == slot).count() {
+				1 => {}
+				0 => return Err(format!("the build is missing a {}", slot.name())),
+				_ => return Err(format!("the build has more than one {}", slot.name())),
+			}
+		}
+		Ok(ArtifactBuild { pieces })
+	}
+
+	// Flat HP flower and ATK plume, and the given sands, goblet and circlet
+	pub fn layout(sands: ArtifactStat, goblet: ArtifactStat, circlet: ArtifactStat) -> Result<Self, String> {
+		ArtifactBuild::new(vec![
+			Artifact::bare(Slot::Flower, ArtifactStat::Hp)?,
+			Artifact::bare(Slot::Plume, ArtifactStat::Atk)?,
+			Artifact::bare(Slot::Sands, sands)?,
+			Artifact::bare(Slot::Goblet, goblet)?,
+			Artifact::bare(Slot::Circlet, circlet)?,
+		])
+	}
+
+	pub fn pieces(&self) -> &[Artifact] {
+		&self.pieces
+	}
+
+	// What the pieces' mainstats add up to
+	pub fn mainstats(&self) -> Mainstats {
+		let mut mainstats = [0.0; 8];
+		for piece in &self.pieces {
+			if let Some(i) = piece.main_stat.mainstat_index() {
+				mainstats[i] += piece.main_stat.main_value();
+			}
+		}
+		mainstats
+	}
+
+	// Rolls summed over the pieces, flat rolls are left out
+	pub fn substats(&self) -> Substats {
+		let mut substats = [0; 7];
+		for (stat, rolls) in self.pieces.iter().flat_map(|x| &x.substats) {
+			if let Some(i) = stat.substat_index() {
+				substats[i] += rolls;
+			}
+		}
+		substats
+	}
+}
+
+impl fmt::Display for ArtifactBuild {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		let parts: Vec<String> = self.pieces[2..].iter().map(|x| x.main_stat.to_string()).collect();
+		write!(f, "{}", parts.join(" / "))
+	}
+}
+
+// Every legal sands/goblet/circlet layout whose mainstats pass `keep`. A slot
+// where nothing passes keeps its first mainstat, and layouts adding up to the
+// same as an earlier one (EM sands + HP goblet, HP sands + EM goblet) are
+// left out.
+pub fn layouts(keep: impl Fn(ArtifactStat) -> bool) -> Vec<ArtifactBuild> {
+	let options = |slot: Slot| {
+		let kept: Vec<ArtifactStat> = slot.main_stats().iter().copied().filter(|x| keep(*x)).collect();
+		match kept.is_empty() {
+			true => vec![slot.main_stats()[0]],
+			false => kept
+		}
+	};
+
+	let mut layouts: Vec<ArtifactBuild> = Vec::new();
+	for sands in options(Slot::Sands) {
+		for goblet in options(Slot::Goblet) {
+			for circlet in options(Slot::Circlet) {
+				let layout = ArtifactBuild::layout(sands, goblet, circlet).unwrap();
+				if !layouts.iter().any(|x| x.mainstats() == layout.mainstats()) {
+					layouts.push(layout);
+				}
+			}
+		}
+	}
+	layouts
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use ArtifactStat::*;
+
+	#[test]
+	fn slots_are_enforced() {
+		assert!(Artifact::bare(Slot::Circlet, ElementalDmg).is_err());
+		assert!(Artifact::bare(Slot::Sands, CritRate).is_err());
+		assert!(Artifact::bare(Slot::Flower, Atk).is_err());
+		assert!(Artifact::new(Slot::Circlet, None, CritRate, vec![(CritRate, 1)]).is_err());
+		assert!(Artifact::new(Slot::Circlet, None, CritRate, vec![(CritDamage, 6), (Em, 4)]).is_err());
+		assert!(Artifact::new(Slot::Circlet, None, CritRate, vec![(CritDamage, 7)]).is_err());
+		assert!(Artifact::new(Slot::Circlet, None, CritDamage, vec![(CritRate, 6), (Atk, 3)]).is_err());
+		assert!(Artifact::new(Slot::Circlet, None, CritDamage, vec![(CritRate, 6), (Atk, 1)]).is_ok());
+		assert!(Artifact::new(Slot::Goblet, None, ElementalDmg, vec![(Healing, 1)]).is_err());
+
+		let flower = Artifact::bare(Slot::Flower, Hp).unwrap();
+		assert!(ArtifactBuild::new(vec![flower.clone(); 5]).unwrap_err().contains("more than one flower"));
+		assert!(ArtifactBuild::new(vec![flower]).unwrap_err().contains("missing a plume"));
+	}
+
+	#[test]
+	fn stats_come_from_the_pieces() {
+		let build = ArtifactBuild::layout(Em, ElementalDmg, CritDamage).unwrap();
+		assert_eq!(build.mainstats(), [187.0, 0.0, 0.0, 46.6, 0.0, 62.2, 0.0, 0.0]);
+		assert_eq!(build.to_string(), "EM / BONUS% / CD");
+
+		let mut pieces = build.pieces().to_vec();
+		pieces[4] = Artifact::new(Slot::Circlet, Some("obsidian"), CritDamage, vec![(CritRate, 5), (Atk, 2), (Em, 1)]).unwrap();
+		pieces[0] = Artifact::new(Slot::Flower, Some("obsidian"), Hp, vec![(CritRate, 2), (CritDamage, 3)]).unwrap();
+		let build = ArtifactBuild::new(pieces).unwrap();
+		assert_eq!(build.substats(), [0, 0, 1, 7, 3, 0, 0]);
+	}
+
+	#[test]
+	fn enumerates_layouts() {
+		// 5 sands, 5 goblets and 7 circlets make 175, but only 101 different sums
+		let all = layouts(|_| true);
+		assert_eq!(all.len(), 101);
+		assert!(all.iter().all(|x| x.pieces()[4].main_stat != ElementalDmg));
+
+		// Only EM and crit: the goblet has nothing better than EM
+		let scaling = layouts(|x| matches!(x, Em | CritRate | CritDamage));
+		let names: Vec<String> = scaling.iter().map(|x| x.to_string()).collect();
+		assert_eq!(names, ["EM / EM / EM", "EM / EM / CR", "EM / EM / CD"]);
+	}
+}
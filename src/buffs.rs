@@ -1,69 +1,273 @@
-use crate::CharStats;
+use serde::Serialize;
+
+use crate::{CharStats, Element};
+
+// Where a buff comes from, mostly so that reports can group them
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Source {
+	Weapon,
+	Artifact,
+	Character,
+	Resonance,
+}
+
+// Send + Sync so that rotations can be evaluated on multiple threads
+pub trait Buff: Send + Sync {
+	fn name(&self) -> &str;
+	fn source(&self) -> Source;
+
+	// Fraction of the rotation the buff is up for
+	fn uptime(&self) -> f32 {
+		1.0
+	}
+
+	fn apply(&self, base: CharStats, stats: CharStats) -> CharStats;
+
+	// Stat conversions (ATK from EM, DMG from HP, ...) run once every buff has
+	// been applied. They read off of `pre`, the stats before any conversion,
+	// so the order they're listed in doesn't matter. Reading `stats` instead
+	// chains conversions, only do that where the game does.
+	fn convert(&self, _base: CharStats, _pre: CharStats, stats: CharStats) -> CharStats {
+		stats
+	}
+
+	// Buffs in the same group never stack. By default that's only copies of
+	// the same buff, `None` for the ones that really do add up.
+	fn group(&self) -> Option<&str> {
+		Some(self.name())
+	}
+
+	// Whatever the buff needs from the rest of the team
+	fn check(&self) -> Result<(), String> {
+		Ok(())
+	}
+
+	fn with_uptime(self, uptime: f32) -> Partial<Self> where Self: Sized {
+		assert!((0.0..=1.0).contains(&uptime));
+		Partial { buff: self, uptime }
+	}
+
+	fn boxed<'a>(self) -> Box<dyn Buff + 'a> where Self: Sized + 'a {
+		Box::new(self)
+	}
+}
+
+pub type BoxedBuff = Box<dyn Buff>;
+
+impl<T: Buff + ?Sized> Buff for &T {
+	fn name(&self) -> &str { (**self).name() }
+	fn source(&self) -> Source { (**self).source() }
+	fn uptime(&self) -> f32 { (**self).uptime() }
+	fn apply(&self, base: CharStats, stats: CharStats) -> CharStats { (**self).apply(base, stats) }
+	fn convert(&self, base: CharStats, pre: CharStats, stats: CharStats) -> CharStats { (**self).convert(base, pre, stats) }
+	fn group(&self) -> Option<&str> { (**self).group() }
+	fn check(&self) -> Result<(), String> { (**self).check() }
+}
+
+impl<T: Buff + ?Sized> Buff for Box<T> {
+	fn name(&self) -> &str { (**self).name() }
+	fn source(&self) -> Source { (**self).source() }
+	fn uptime(&self) -> f32 { (**self).uptime() }
+	fn apply(&self, base: CharStats, stats: CharStats) -> CharStats { (**self).apply(base, stats) }
+	fn convert(&self, base: CharStats, pre: CharStats, stats: CharStats) -> CharStats { (**self).convert(base, pre, stats) }
+	fn group(&self) -> Option<&str> { (**self).group() }
+	fn check(&self) -> Result<(), String> { (**self).check() }
+}
+
+// The usual way of making a buff, a name and a source on top of the closure
+pub struct Named<F> {
+	name: &'static str,
+	source: Source,
+	group: Option<&'static str>,
+	effect: F,
+}
+
+pub fn named<F: Fn(CharStats, CharStats) -> CharStats + Send + Sync>(
+	name: &'static str,
+	source: Source,
+	effect: F
+) -> Named<F> {
+	Named { name, source, group: Some(name), effect }
+}
+
+impl<F> Named<F> {
+	// i.e. the 2pc and 4pc bonus of a set
+	pub fn in_group(mut self, group: &'static str) -> Self {
+		self.group = Some(group);
+		self
+	}
+
+	pub fn stacking(mut self) -> Self {
+		self.group = None;
+		self
+	}
+}
+
+impl<F: Fn(CharStats, CharStats) -> CharStats + Send + Sync> Buff for Named<F> {
+	fn name(&self) -> &str { self.name }
+	fn source(&self) -> Source { self.source }
+	fn group(&self) -> Option<&str> { self.group }
+	fn apply(&self, base: CharStats, stats: CharStats) -> CharStats { (self.effect)(base, stats) }
+}
+
+// A buff that only converts stats, see `Buff::convert`
+pub struct Conversion<F> {
+	name: &'static str,
+	source: Source,
+	effect: F,
+}
+
+pub fn conversion<F: Fn(CharStats, CharStats, CharStats) -> CharStats + Send + Sync>(
+	name: &'static str,
+	source: Source,
+	effect: F
+) -> Conversion<F> {
+	Conversion { name, source, effect }
+}
+
+impl<F: Fn(CharStats, CharStats, CharStats) -> CharStats + Send + Sync> Buff for Conversion<F> {
+	fn name(&self) -> &str { self.name }
+	fn source(&self) -> Source { self.source }
+	fn apply(&self, _base: CharStats, stats: CharStats) -> CharStats { stats }
+	fn convert(&self, base: CharStats, pre: CharStats, stats: CharStats) -> CharStats { (self.effect)(base, pre, stats) }
+}
+
+// Only up for part of the rotation. Instead of working out every hit
+// separately the difference the buff makes is scaled by its uptime.
+pub struct Partial<B> {
+	buff: B,
+	uptime: f32,
+}
+
+impl<B: Buff> Buff for Partial<B> {
+	fn name(&self) -> &str { self.buff.name() }
+	fn source(&self) -> Source { self.buff.source() }
+	fn uptime(&self) -> f32 { self.uptime * self.buff.uptime() }
+	fn group(&self) -> Option<&str> { self.buff.group() }
+	fn check(&self) -> Result<(), String> { self.buff.check() }
+	fn apply(&self, base: CharStats, stats: CharStats) -> CharStats {
+		stats.blend(self.buff.apply(base, stats), self.uptime)
+	}
+	fn convert(&self, base: CharStats, pre: CharStats, stats: CharStats) -> CharStats {
+		stats.blend(self.buff.convert(base, pre, stats), self.uptime)
+	}
+}
+
+// Elemental resonance, only there with two characters of the element
+pub struct Resonance {
+	name: &'static str,
+	element: Element,
+	party: Vec<Element>,
+	effect: fn(CharStats, CharStats) -> CharStats,
+}
+
+impl Buff for Resonance {
+	fn name(&self) -> &str { self.name }
+	fn source(&self) -> Source { Source::Resonance }
+	fn apply(&self, base: CharStats, stats: CharStats) -> CharStats { (self.effect)(base, stats) }
+
+	fn check(&self) -> Result<(), String> {
+		match self.party.iter().filter(|x| **x == self.element).count() {
+			2.. => Ok(()),
+			_ => Err(format!("'{}' needs two {:?} characters, the party is {:?}", self.name, self.element, self.party))
+		}
+	}
+}
+
+// The first reason the buffs can't all be active at once, if any
+pub fn validate(buffs: &[Box<dyn Buff + '_>]) -> Result<(), String> {
+	for (i, buff) in buffs.iter().enumerate() {
+		buff.check()?;
+		let Some(group) = buff.group() else { continue };
+		if let Some(other) = buffs[..i].iter().find(|x| x.group() == Some(group)) {
+			return Err(match other.name() == buff.name() {
+				true => format!("'{}' is listed twice, it doesn't stack with itself", buff.name()),
+				false => format!("'{}' doesn't stack with '{}'", buff.name(), other.name())
+			});
+		}
+	}
+	Ok(())
+}
+
+// What ended up in a build, for the reports
+#[derive(Clone, Debug, Serialize)]
+pub struct ActiveBuff {
+	pub name: String,
+	pub source: Source,
+	pub uptime: f32,
+}
+
+impl ActiveBuff {
+	pub fn of(buff: &dyn Buff) -> Self {
+		ActiveBuff {
+			name: buff.name().to_string(),
+			source: buff.source(),
+			uptime: buff.uptime(),
+		}
+	}
+}
 
 /////////////////////////////
 ///////// WEAPONS ///////////
 /////////////////////////////
 
+pub type BoxedEffect = Box<dyn Fn(CharStats, CharStats) -> CharStats + Send + Sync>;
+pub type BoxedConversion = Box<dyn Fn(CharStats, CharStats, CharStats) -> CharStats + Send + Sync>;
+
+// A weapon passive without a name, the registry in `weapons.rs` gives it one.
+// Most are plain closures, the ones converting stats declare it separately.
+pub struct Passive {
+	pub effect: BoxedEffect,
+	pub conversion: Option<BoxedConversion>,
+}
+
+impl Passive {
+	pub fn converting(
+		effect: impl Fn(CharStats, CharStats) -> CharStats + Send + Sync + 'static,
+		conversion: impl Fn(CharStats, CharStats, CharStats) -> CharStats + Send + Sync + 'static
+	) -> Self {
+		Passive { effect: Box::new(effect), conversion: Some(Box::new(conversion)) }
+	}
+}
+
 pub trait MagicBoxed {
 	type Source;
 	type Target;
 	fn boxed(self) -> Self::Target;
 }
 
-impl<T: Fn(CharStats, CharStats) -> CharStats + 'static> MagicBoxed for T {
+impl<T: Fn(CharStats, CharStats) -> CharStats + Send + Sync + 'static> MagicBoxed for T {
 	type Source = T;
-	type Target = Box<dyn Fn(CharStats, CharStats) -> CharStats>;
+	type Target = Passive;
 	fn boxed(self) -> Self::Target {
-		Box::new(self)
+		Passive { effect: Box::new(self), conversion: None }
 	}
 }
 
 /// CLAYMORES ///
-pub fn earth_shaker_base(
-	mut stats: CharStats,
-) -> CharStats {
-	stats.atk += 565.0;
-	stats
-}
-
 pub fn earth_shaker_buff(
 	refinement: usize,
 ) -> impl Fn(CharStats, CharStats) -> CharStats {
 	assert!(refinement >= 1);
 	assert!(refinement <= 5);
-	move |base, mut stats| {
-		stats.atk += base.atk * 0.276;
+	move |_, mut stats| {
 		stats.skill_bonus += 12.0 + 4.0 * refinement as f32;
 		stats
 	}
 }
 
-pub fn wgs_base(
-	mut stats: CharStats,
-) -> CharStats {
-	stats.atk += 608.0;
-	stats
-}
-
 pub fn wgs_buff(
 	refinement: usize
 ) -> impl Fn(CharStats, CharStats) -> CharStats {
 	assert!(refinement >= 1);
 	assert!(refinement <= 5);
 	move |base, mut stats| {
-		stats.atk += base.atk * 0.496;
 		stats.atk += base.atk * (0.15 + 0.05 * refinement as f32);
 		stats
 	}
 }
 
-pub fn tidal_shadow_base(
-	mut stats: CharStats,
-) -> CharStats {
-	stats.atk += 510.0;
-	stats
-}
-
 pub fn tidal_shadow_buff(
 	refinement: usize,
 	healed: bool
@@ -71,7 +275,6 @@ pub fn tidal_shadow_buff(
 	assert!(refinement >= 1);
 	assert!(refinement <= 5);
 	move |base, mut stats| {
-		stats.atk += base.atk * 0.413;
 		if healed {
 			stats.atk += base.atk * (0.18 + 0.06 * refinement as f32);
 		}
@@ -79,14 +282,6 @@ pub fn tidal_shadow_buff(
 	}
 }
 
-pub fn mailed_flower_base(
-	mut stats: CharStats,
-) -> CharStats {
-	stats.atk += 565.0;
-	stats.em += 110.0;
-	stats
-}
-
 pub fn mailed_flower_buff(
 	refinement: usize,
 	buff: bool
@@ -102,14 +297,6 @@ pub fn mailed_flower_buff(
 	}
 }
 
-pub fn serpent_spine_base(
-	mut stats: CharStats,
-) -> CharStats {
-	stats.atk += 510.0;
-	stats.crit_rate += 27.6;
-	stats
-}
-
 pub fn serpent_spine_buff(
 	refinement: usize,
 	stacks: usize
@@ -124,14 +311,6 @@ pub fn serpent_spine_buff(
 	}
 }
 
-pub fn rainslasher_base(
-	mut stats: CharStats,
-) -> CharStats {
-	stats.atk += 510.0;
-	stats.em += 165.0;
-	stats
-}
-
 pub fn rainslasher_buff(
 	refinement: usize,
 	buff: bool
@@ -146,14 +325,6 @@ pub fn rainslasher_buff(
 	}
 }
 
-pub fn sun_base(
-	mut stats: CharStats,
-) -> CharStats {
-	stats.atk += 741.0;
-	stats.crit_rate += 11.0;
-	stats
-}
-
 pub fn sun_buff(
 	refinement: usize,
 	buff: bool,
@@ -177,47 +348,20 @@ pub fn sun_buff(
 }
 
 /// CATALYSTS ///
-
-pub fn empty_base(
-	mut base: CharStats,
-) -> CharStats {
-	base
-}
-
-pub fn empty_buff(
-	base: CharStats,
-	mut stats: CharStats,
-) -> CharStats {
-	stats
-}
-
-pub fn magic_guide_base(
-	mut base: CharStats,
-) -> CharStats {
-	base.atk += 354.0;
-	base.em += 187.0;
-	base
-}
-
 pub fn magic_guide_buff(
+	refinement: usize,
 	hydro_aura: bool,
 ) -> impl Fn(CharStats, CharStats) -> CharStats {
-	move |base, mut stats| {
+	assert!(refinement >= 1);
+	assert!(refinement <= 5);
+	move |_, mut stats| {
 		if hydro_aura {
-			stats.dmg_bonus += 24.0;
+			stats.dmg_bonus += 9.0 + 3.0 * refinement as f32;
 		}
 		stats
 	}
 }
 
-pub fn sac_jade_base(
-	mut stats: CharStats,
-) -> CharStats {
-	stats.crit_rate += 36.8;
-	stats.atk += 454.0;
-	stats
-}
-
 pub fn sac_jade_buff(
 	refinement: usize,
 ) -> impl Fn(CharStats, CharStats) -> CharStats {
@@ -230,29 +374,17 @@ pub fn sac_jade_buff(
 	}
 }
 
-pub fn soss_base(
-	mut stats: CharStats,
-) -> CharStats {
-	stats.crit_rate += 44.1;
-	stats.atk += 542.0;
-	stats
-}
-
 pub fn soss_buff(
+	refinement: usize,
 	stacks: usize
-) -> impl Fn(CharStats, CharStats) -> CharStats {
-	move |_, mut stats| {
-		stats.atk += 0.52 * stats.em;
-		stats.atk += 0.28 * stacks as f32 * stats.em;
+) -> Passive {
+	assert!(refinement >= 1);
+	assert!(refinement <= 5);
+	Passive::converting(|_, stats| stats, move |_, pre, mut stats| {
+		stats.atk += (0.39 + 0.13 * refinement as f32) * pre.em;
+		stats.atk += (0.21 + 0.07 * refinement as f32) * stacks as f32 * pre.em;
 		stats
-	}
-}
-
-pub fn missive_base(
-	mut stats: CharStats
-) -> CharStats {
-	stats.atk += 510.0;
-	stats
+	})
 }
 
 pub fn missive_buff(
@@ -260,41 +392,32 @@ pub fn missive_buff(
 ) -> impl Fn(CharStats, CharStats) -> CharStats {
 	assert!(refinement >= 1);
 	assert!(refinement <= 5);
-	move |base, mut stats| {
-		stats.atk += 0.413 * base.atk;
+	move |_, mut stats| {
 		stats.atk += 0.09 + 0.03 * refinement as f32;
 		stats.em += 36.0 + 12.0 * refinement as f32;
 		stats
 	}
 }
 
-pub fn homa_base(
-	mut stats: CharStats
-) -> CharStats {
-	stats.crit_damage += 66.4;
-	stats.atk += 608.0;
-	stats
-}
-
 pub fn homa_buff(
+	refinement: usize,
 	under_half_hp: bool
-) -> impl Fn(CharStats, CharStats) -> CharStats {
-	move |base, mut stats| {
-		stats.hp += base.hp * 0.2;
-		stats.atk += match under_half_hp {
-			true => stats.hp * 0.018,
-			false => stats.hp * 0.008
-		};
-		stats
-	}
-}
-
-pub fn lumidouce_base(
-	mut stats: CharStats
-) -> CharStats {
-	stats.crit_rate += 33.1;
-	stats.atk += 608.0;
-	stats
+) -> Passive {
+	assert!(refinement >= 1);
+	assert!(refinement <= 5);
+	Passive::converting(
+		move |base, mut stats| {
+			stats.hp += base.hp * (0.15 + 0.05 * refinement as f32);
+			stats
+		},
+		move |_, pre, mut stats| {
+			stats.atk += match under_half_hp {
+				true => pre.hp * (0.014 + 0.004 * refinement as f32),
+				false => pre.hp * (0.006 + 0.002 * refinement as f32)
+			};
+			stats
+		}
+	)
 }
 
 pub fn lumidouce_buff(
@@ -311,33 +434,17 @@ pub fn lumidouce_buff(
 	}
 }
 
-pub fn dragons_base(
-	mut stats: CharStats
-) -> CharStats {
-	stats.em += 221.0;
-	stats.atk += 454.0;
-	stats
-}
-
 pub fn dragons_buff(
 	refinement: usize
 ) -> impl Fn(CharStats, CharStats) -> CharStats {
 	assert!(refinement >= 1);
 	assert!(refinement <= 5);
-	move |base, mut stats| {
+	move |_, mut stats| {
 		stats.dmg_bonus += 16.0 + refinement as f32 * 4.0;
 		stats
 	}
 }
 
-pub fn surfing_time_base(
-	mut stats: CharStats
-) -> CharStats {
-	stats.crit_damage += 88.2;
-	stats.atk += 542.0;
-	stats
-}
-
 pub fn surfing_time_buff(
 	refinement: usize,
 	stacks: usize
@@ -354,14 +461,6 @@ pub fn surfing_time_buff(
 	}
 }
 
-pub fn tome_base(
-	mut base: CharStats,
-) -> CharStats {
-	base.crit_damage += 88.2;
-	base.atk += 542.0;
-	base
-}
-
 pub fn tome_buff(
 	refinement: usize,
 	stacks: usize
@@ -376,14 +475,6 @@ pub fn tome_buff(
 	}
 }
 
-pub fn solar_pearl_base(
-	mut base: CharStats,
-) -> CharStats {
-	base.crit_rate += 27.6;
-	base.atk += 510.0;
-	base
-}
-
 pub fn solar_pearl_buff(
 	refinement: usize
 ) -> impl Fn(CharStats, CharStats) -> CharStats {
@@ -395,22 +486,6 @@ pub fn solar_pearl_buff(
 	}
 }
 
-pub fn widsith_base(
-	mut base: CharStats,
-) -> CharStats {
-	base.crit_damage += 55.1;
-	base.atk += 510.0;
-	base
-}
-
-pub fn floating_dreams_base(
-	mut base: CharStats,
-) -> CharStats {
-	base.em += 265.0;
-	base.atk += 542.0;
-	base
-}
-
 pub fn floating_dreams_buff(
 	refinement: usize,
 	same_types_count: usize,
@@ -421,7 +496,7 @@ pub fn floating_dreams_buff(
 
 	move |_, mut stats| {
 		stats.em += (24.0 + 8.0 * refinement as f32) * same_types_count as f32;
-		stats.dmg_bonus += (6.0 + 4.0 * refinement as f32) * other_types_count as f32;
+		stats.elemental_bonus.add_elemental((6.0 + 4.0 * refinement as f32) * other_types_count as f32);
 		stats
 	}
 }
@@ -435,7 +510,7 @@ pub fn widsith_buff(
 	move |base, mut stats| {
 		match variant {
 			0 => stats.atk += (0.45 + 0.15 * refinement as f32) * base.atk,
-			1 => stats.dmg_bonus += 36.0 + 12.0 * refinement as f32,
+			1 => stats.elemental_bonus.add_elemental(36.0 + 12.0 * refinement as f32),
 			2 => stats.em += 180.0 + 60.0 * refinement as f32,
 			3 => (),
 			_ => panic!("Invalid Widsith variant!")
@@ -445,14 +520,6 @@ pub fn widsith_buff(
 	}
 }
 
-pub fn tulaytullah_base(
-	mut base: CharStats,
-) -> CharStats {
-	base.crit_damage += 44.1;
-	base.atk += 674.0;
-	base
-}
-
 pub fn tulaytullah_buff(
 	refinement: usize,
 	stacks_time: usize,
@@ -469,14 +536,6 @@ pub fn tulaytullah_buff(
 	}
 }
 
-pub fn prayer_base(
-	mut base: CharStats,
-) -> CharStats {
-	base.crit_rate += 33.1;
-	base.atk += 608.0;
-	base
-}
-
 pub fn prayer_buff(
 	refinement: usize,
 	stacks: usize
@@ -484,31 +543,22 @@ pub fn prayer_buff(
 	assert!(refinement >= 1);
 	assert!(refinement <= 5);
 	move |_, mut stats| {
-		stats.dmg_bonus += (6.0 + 2.0 * refinement as f32) * stacks as f32;
+		stats.elemental_bonus.add_elemental((6.0 + 2.0 * refinement as f32) * stacks as f32);
 		stats
 	}
 }
 
-pub fn ceiba_base(
-	mut base: CharStats,
-) -> CharStats {
-	base.atk += 510.0;
-	// the hp% buff doesn't count as base hp, moved into the buff section
-	base
-}
-
 pub fn ceiba_buff(
 	refinement: usize
-) -> impl Fn(CharStats, CharStats) -> CharStats {
+) -> Passive {
 	assert!(refinement >= 1);
 	assert!(refinement <= 5);
-	move |base, mut stats| {
+	Passive::converting(|_, stats| stats, move |_, pre, mut stats| {
 		let max_increase = 12.0 + 4.0 * refinement as f32;
-		let increase = (0.5 + 0.1 * refinement as f32) * (stats.hp / 1000.0).floor(); // 5% per every 1000hp
-		stats.hp += 0.413 * base.hp;
+		let increase = (0.5 + 0.1 * refinement as f32) * (pre.hp / 1000.0).floor(); // 5% per every 1000hp
 		stats.na_bonus += increase.clamp(0.0, max_increase);
 		stats
-	}
+	})
 }
 
 /////////////////////////////
@@ -517,302 +567,385 @@ pub fn ceiba_buff(
 
 pub fn nahida_burst(
 	em: f32
-) -> impl Fn(CharStats, CharStats) -> CharStats {
-	move |_, mut stats| {
+) -> impl Buff {
+	named("Nahida Burst", Source::Character, move |_, mut stats| {
 		stats.em += 0.25 * em;
 		stats
-	}
+	})
 }
 
+// Geo is always shredded, the rest depends on the party
 pub fn xilonen_shred(
-	_base: CharStats,
-	mut stats: CharStats
-) -> CharStats {
-	stats.res_shred += 36.0;
-	stats
-}
-
-pub fn vv_shred(
-	_base: CharStats,
-	mut stats: CharStats
-) -> CharStats {
-	stats.res_shred += 40.0;
-	stats
+	party_elements: &[Element]
+) -> impl Buff {
+	let party_elements = party_elements.to_vec();
+	named("Xilonen Shred", Source::Character, move |_, mut stats| {
+		stats.res_shred[Element::Geo] += 36.0;
+		for element in &party_elements {
+			if *element != Element::Geo {
+				stats.res_shred[*element] += 36.0;
+			}
+		}
+		stats
+	})
 }
 
 pub fn kazuha_e(
-	_base: CharStats,
-	mut stats: CharStats
-) -> CharStats {
-	stats.dmg_bonus += 36.0; // 900 em kazuha
-	stats
+	swirled: Element
+) -> impl Buff {
+	named("Kazuha A4", Source::Character, move |_, mut stats| {
+		stats.elemental_bonus[swirled] += 36.0; // 900 em kazuha
+		stats
+	})
 }
 
-pub fn thoma_c6(
-	_base: CharStats,
-	mut stats: CharStats
-) -> CharStats {
-	stats.na_bonus += 15.0;
-	stats
+pub fn thoma_c6() -> impl Buff {
+	named("Thoma C6", Source::Character, |_, mut stats| {
+		stats.na_bonus += 15.0;
+		stats
+	})
 }
 
-pub fn zhong_shred(
-	_base: CharStats,
-	mut stats: CharStats
-) -> CharStats {
-	stats.res_shred += 20.0;
-	stats
+pub fn zhong_shred() -> impl Buff {
+	named("Zhongli Shield", Source::Character, |_, mut stats| {
+		stats.res_shred.add_all(20.0);
+		stats
+	})
 }
 
-pub fn tao_skill(
-	_base: CharStats,
-	mut stats: CharStats
-) -> CharStats {
-	stats.atk += stats.hp * 0.0626;
-	stats
+pub fn tao_skill() -> impl Buff {
+	conversion("Hu Tao Skill", Source::Character, |_, pre, mut stats| {
+		stats.atk += pre.hp * 0.0626;
+		stats
+	})
 }
 
-pub fn tao_a4(
-	_base: CharStats,
-	mut stats: CharStats
-) -> CharStats {
-	stats.dmg_bonus += 33.0;
-	stats
+pub fn tao_a4() -> impl Buff {
+	named("Hu Tao A4", Source::Character, |_, mut stats| {
+		stats.elemental_bonus[Element::Pyro] += 33.0;
+		stats
+	})
 }
 
-pub fn bennett_burst(
-	_base: CharStats,
-	mut stats: CharStats
-) -> CharStats {
-	stats.atk += 1000.0;
-	stats.dmg_bonus += 15.0;
-	stats
+pub fn bennett_burst() -> impl Buff {
+	named("Bennett Burst", Source::Character, |_, mut stats| {
+		stats.atk += 1000.0;
+		stats.elemental_bonus[Element::Pyro] += 15.0;
+		stats
+	})
 }
 
 pub fn furina_burst(
 	fanfare: f32
-) -> impl Fn(CharStats, CharStats) -> CharStats {
-	move |_, mut stats| {
+) -> impl Buff {
+	named("Furina Burst", Source::Character, move |_, mut stats| {
 		stats.dmg_bonus += 0.25 * fanfare;
 		stats
-	}
+	})
+}
+
+// Ramps up to 50% over the 15s of her burst, so it's up half the time on average
+pub fn yelan_a4() -> impl Buff {
+	named("Yelan A4", Source::Character, |_, mut stats| {
+		stats.dmg_bonus += 50.0;
+		stats
+	}).with_uptime(0.5)
+}
+
+pub fn albedo_burst() -> impl Buff {
+	named("Albedo Burst", Source::Character, |_, mut stats| {
+		stats.em += 120.0;
+		stats
+	})
 }
 
-pub fn yelan_a4(
-	_base: CharStats,
-	mut stats: CharStats
-) -> CharStats {
-	stats.dmg_bonus += 25.0; // averaged out
-	stats
+// Scales off Yun Jin's own DEF, so it takes her stats
+pub fn yun_burst(
+	yun: &CharStats
+) -> impl Buff {
+	let yun_def = yun.def;
+	named("Yun Jin Burst", Source::Character, move |_, mut stats| {
+		stats.na_bonus += 12.0;
+		stats.na_bonus_flat += 0.79 * yun_def; // burst lvl. 13
+		stats
+	})
 }
 
+// The party includes whoever the stats are for
 pub fn pyro_resonance(
-	base: CharStats,
-	mut stats: CharStats
-) -> CharStats {
-	stats.atk += base.atk * 0.25;
-	stats
+	party: &[Element]
+) -> impl Buff {
+	Resonance {
+		name: "Pyro Resonance",
+		element: Element::Pyro,
+		party: party.to_vec(),
+		effect: |base, mut stats| {
+			stats.atk += base.atk * 0.25;
+			stats
+		}
+	}
 }
 
 pub fn hydro_resonance(
-	base: CharStats,
-	mut stats: CharStats
-) -> CharStats {
-	stats.hp += base.hp * 0.25;
-	stats
+	party: &[Element]
+) -> impl Buff {
+	Resonance {
+		name: "Hydro Resonance",
+		element: Element::Hydro,
+		party: party.to_vec(),
+		effect: |base, mut stats| {
+			stats.hp += base.hp * 0.25;
+			stats
+		}
+	}
 }
 
 /////////////////////////////
 //////// Artifacts //////////
 /////////////////////////////
 
-pub fn hod2pc(
-	_base: CharStats,
-	mut stats: CharStats
-) -> CharStats {
-	stats.dmg_bonus += 15.0;
-	stats
+pub fn hod2pc() -> impl Buff {
+	named("Heart of Depth 2pc", Source::Artifact, |_, mut stats| {
+		stats.elemental_bonus[Element::Hydro] += 15.0;
+		stats
+	}).in_group("Heart of Depth")
 }
 
-pub fn hod(
-	base: CharStats,
-	mut stats: CharStats
-) -> CharStats {
-	stats = hod2pc(base, stats);
-	stats.na_bonus += 30.0;
-	stats.ca_bonus += 30.0;
-	stats
+pub fn hod() -> impl Buff {
+	named("Heart of Depth 4pc", Source::Artifact, |base, stats| {
+		let mut stats = hod2pc().apply(base, stats);
+		stats.na_bonus += 30.0;
+		stats.ca_bonus += 30.0;
+		stats
+	}).in_group("Heart of Depth")
 }
 
-pub fn reverie2pc(
-	base: CharStats,
-	mut stats: CharStats
-) -> CharStats {
-	stats.atk += base.atk * 0.18;
-	stats
+pub fn reverie2pc() -> impl Buff {
+	named("Crimson Reverie 2pc", Source::Artifact, |base, mut stats| {
+		stats.atk += base.atk * 0.18;
+		stats
+	}).in_group("Crimson Reverie")
 }
 
 pub fn reverie(
 	stacks: usize
-) -> impl Fn(CharStats, CharStats) -> CharStats {
-	move |base, stats| {
-		let mut stats = reverie2pc(base, stats);
+) -> impl Buff {
+	named("Crimson Reverie 4pc", Source::Artifact, move |base, stats| {
+		let mut stats = reverie2pc().apply(base, stats);
 		stats.dmg_bonus += stacks.min(5) as f32 * 10.0;
 		stats
-	}
+	}).in_group("Crimson Reverie")
 }
 
-pub fn troupe2pc(
-	_base: CharStats,
-	mut stats: CharStats
-) -> CharStats {
-	stats.skill_bonus += 20.0;
-	stats
+pub fn troupe2pc() -> impl Buff {
+	named("Gilded Troupe 2pc", Source::Artifact, |_, mut stats| {
+		stats.skill_bonus += 20.0;
+		stats
+	}).in_group("Gilded Troupe")
 }
 
 pub fn troupe(
 	is_off_field: bool
-) -> impl Fn(CharStats, CharStats) -> CharStats {
-	move |base, stats| {
-		let mut stats = troupe2pc(base, stats);
+) -> impl Buff {
+	named("Gilded Troupe 4pc", Source::Artifact, move |base, stats| {
+		let mut stats = troupe2pc().apply(base, stats);
 		stats.skill_bonus += 25.0;
 		if is_off_field {
 			stats.skill_bonus += 25.0;
 		}
 		stats
-	}
+	}).in_group("Gilded Troupe")
 }
 
-pub fn gilded2pc(
-	_base: CharStats,
-	mut stats: CharStats
-) -> CharStats {
-	stats.em += 80.0;
-	stats
+pub fn gilded2pc() -> impl Buff {
+	named("Gilded Dreams 2pc", Source::Artifact, |_, mut stats| {
+		stats.em += 80.0;
+		stats
+	}).in_group("Gilded Dreams")
 }
 
 pub fn gilded(
 	same_type: usize,
 	other_type: usize
-) -> impl Fn(CharStats, CharStats) -> CharStats {
-	move |base, stats| {
-		let mut stats = gilded2pc(base, stats);
+) -> impl Buff {
+	named("Gilded Dreams 4pc", Source::Artifact, move |base, stats| {
+		let mut stats = gilded2pc().apply(base, stats);
 		stats.atk += 0.14 * same_type as f32 * base.atk;
 		stats.em += 50.0 * other_type as f32;
 		stats
-	}
+	}).in_group("Gilded Dreams")
+}
+
+pub fn bollide() -> impl Buff {
+	named("Lavawalker's Bolide", Source::Artifact, |_, mut stats| {
+		stats.na_bonus += 40.0;
+		stats.ca_bonus += 40.0;
+		stats
+	})
+}
+
+pub fn instructor2() -> impl Buff {
+	named("Instructor 2pc", Source::Artifact, |_, mut stats| {
+		stats.em += 80.0;
+		stats
+	}).in_group("Instructor")
 }
 
-pub fn bollide(
-	_: CharStats,
-	mut stats: CharStats
-) -> CharStats {
-	stats.na_bonus += 40.0;
-	stats.ca_bonus += 40.0;
-	stats
+// A teammate's 4pc, which doesn't stack with another 4pc but does with our own 2pc
+pub fn instructor_share() -> impl Buff {
+	named("Instructor 4pc", Source::Artifact, |_, mut stats| {
+		stats.em += 120.0;
+		stats
+	}).in_group("Instructor 4pc")
 }
 
-pub fn instructor2(
-	_base: CharStats,
-	mut stats: CharStats
-) -> CharStats {
-	stats.em += 80.0;
-	stats
+pub fn tenacity2pc() -> impl Buff {
+	named("Tenacity of the Millelith 2pc", Source::Artifact, |base, mut stats| {
+		stats.hp += base.hp * 0.2;
+		stats
+	}).in_group("Tenacity of the Millelith")
 }
 
-pub fn instructor_share(
-	_base: CharStats,
-	mut stats: CharStats
-) -> CharStats {
-	stats.em += 120.0;
-	stats
+// The wearer gets the ATK share too
+pub fn tenacity() -> impl Buff {
+	named("Tenacity of the Millelith 4pc", Source::Artifact, |base, stats| {
+		let mut stats = tenacity2pc().apply(base, stats);
+		stats.atk += base.atk * 0.2;
+		stats
+	}).in_group("Tenacity of the Millelith")
 }
 
-pub fn albedo_burst(
-	_base: CharStats,
-	mut stats: CharStats
-) -> CharStats {
-	stats.em += 120.0;
-	stats
+pub fn mh2pc() -> impl Buff {
+	named("Marechaussee Hunter 2pc", Source::Artifact, |_, mut stats| {
+		stats.na_bonus += 15.0;
+		stats.ca_bonus += 15.0;
+		stats
+	}).in_group("Marechaussee Hunter")
 }
 
-pub fn yun_burst(
-	_base: CharStats,
-	mut stats: CharStats
-) -> CharStats {
-	stats.na_bonus += 12.0;
-	stats.na_bonus_flat += 1660.0; // ~ 2100 DEF
-	stats
-}
-
-pub fn tenacity2pc(
-	base: CharStats,
-	mut stats: CharStats
-) -> CharStats {
-	stats.hp += base.hp * 0.2;
-	stats
-}
-
-pub fn mh2pc(
-	_base: CharStats,
-	mut stats: CharStats
-) -> CharStats {
-	stats.na_bonus += 15.0;
-	stats.ca_bonus += 15.0;
-	stats
-}
-
-pub fn mh(
-	_base: CharStats,
-	mut stats: CharStats
-) -> CharStats {
-	stats = mh2pc(_base, stats);
-	stats.crit_rate += 36.0;
-	stats
-}
-
-pub fn shime2pc(
-	base: CharStats,
-	mut stats: CharStats
-) -> CharStats {
-	stats.atk += 0.18 * base.atk;
-	stats
-}
-
-pub fn shime(
-	_base: CharStats,
-	mut stats: CharStats
-) -> CharStats {
-	stats = shime2pc(_base, stats);
-	stats.na_bonus += 50.0;
-	stats
-}
-
-pub fn obsidian(
-	_base: CharStats,
-	mut stats: CharStats
-) -> CharStats {
-	stats.crit_rate += 40.0;
-	stats.dmg_bonus += 15.0;
-	stats
+pub fn mh() -> impl Buff {
+	named("Marechaussee Hunter 4pc", Source::Artifact, |base, stats| {
+		let mut stats = mh2pc().apply(base, stats);
+		stats.crit_rate += 36.0;
+		stats
+	}).in_group("Marechaussee Hunter")
+}
+
+pub fn shime2pc() -> impl Buff {
+	named("Shimenawa's Reminiscence 2pc", Source::Artifact, |base, mut stats| {
+		stats.atk += 0.18 * base.atk;
+		stats
+	}).in_group("Shimenawa's Reminiscence")
 }
 
+pub fn shime() -> impl Buff {
+	named("Shimenawa's Reminiscence 4pc", Source::Artifact, |base, stats| {
+		let mut stats = shime2pc().apply(base, stats);
+		stats.na_bonus += 50.0;
+		stats
+	}).in_group("Shimenawa's Reminiscence")
+}
+
+pub fn obsidian2pc() -> impl Buff {
+	named("Obsidian Codex 2pc", Source::Artifact, |_, mut stats| {
+		stats.dmg_bonus += 15.0;
+		stats
+	}).in_group("Obsidian Codex")
+}
+
+pub fn obsidian() -> impl Buff {
+	named("Obsidian Codex 4pc", Source::Artifact, |base, stats| {
+		let mut stats = obsidian2pc().apply(base, stats);
+		stats.crit_rate += 40.0;
+		stats
+	}).in_group("Obsidian Codex")
+}
+
+pub fn vv_shred(
+	swirled: Element
+) -> impl Buff {
+	named("Viridescent Venerer 4pc", Source::Artifact, move |_, mut stats| {
+		stats.res_shred[swirled] += 40.0;
+		stats
+	})
+}
+
+// Only the elements involved in the wearer's reaction are buffed
 pub fn scroll(
+	reaction_elements: &[Element],
 	saurian: bool
-) -> impl Fn(CharStats, CharStats) -> CharStats {
-	move |_, mut stats| {
-		stats.dmg_bonus += 12.0;
-		if saurian {
-			stats.dmg_bonus += 28.0;
+) -> impl Buff {
+	let reaction_elements = reaction_elements.to_vec();
+	named("Scroll of the Hero 4pc", Source::Artifact, move |_, mut stats| {
+		for element in &reaction_elements {
+			stats.elemental_bonus[*element] += 12.0;
+			if saurian {
+				stats.elemental_bonus[*element] += 28.0;
+			}
 		}
 		stats
-	}
+	})
 }
 
 pub fn petra_share(
-	_base: CharStats,
-	mut stats: CharStats
-) -> CharStats {
-	stats.dmg_bonus += 35.0;
-	stats
-}
\ No newline at end of file
+	crystallized: Element
+) -> impl Buff {
+	named("Archaic Petra 4pc", Source::Artifact, move |_, mut stats| {
+		stats.elemental_bonus[crystallized] += 35.0;
+		stats
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{characters, stats, weapons};
+	use crate::artifacts::{ArtifactBuild, ArtifactStat};
+
+	// ATK from EM has to come out the same whether the EM buffs were listed
+	// before or after the conversion
+	#[test]
+	fn conversions_ignore_buff_order() {
+		let soss = weapons::by_id("soss").unwrap();
+		let build = ArtifactBuild::layout(ArtifactStat::Em, ArtifactStat::ElementalDmg, ArtifactStat::CritRate).unwrap();
+		let evaluate = |buffs: Vec<Box<dyn Buff>>| stats(
+			characters::FRAUD.base(characters::Level::MAX).unwrap(),
+			|x| soss.base(x),
+			buffs,
+			&build,
+			&[0, 2, 4, 4, 4, 0, 0],
+			&Default::default()
+		).0;
+		let weapon = || soss.buff(1, &Default::default()).unwrap();
+
+		let before = evaluate(vec![nahida_burst(800.0).boxed(), instructor_share().boxed(), weapon()]);
+		let after = evaluate(vec![weapon(), nahida_burst(800.0).boxed(), instructor_share().boxed()]);
+		assert_eq!(before.atk, after.atk);
+		assert!(before.atk > evaluate(vec![weapon()]).atk);
+	}
+
+	// R5 converts twice the EM of R1, as on the weapon page
+	#[test]
+	fn conversions_scale_with_refinement() {
+		let base = characters::FRAUD.base(characters::Level::MAX).unwrap();
+		let pre = CharStats { em: 100.0, ..base };
+		let atk = |refinement| {
+			let soss = soss_buff(refinement, 3);
+			(soss.conversion.as_ref().unwrap())(base, pre, pre).atk - pre.atk
+		};
+		assert!((atk(1) - 136.0).abs() < 1e-3);
+		assert!((atk(5) - 2.0 * atk(1)).abs() < 1e-3);
+	}
+
+	#[test]
+	fn rejects_buffs_that_dont_stack() {
+		let scroll = || scroll(&[Element::Pyro], false).boxed();
+		assert!(validate(&[scroll(), obsidian().boxed()]).is_ok());
+		assert!(validate(&[scroll(), scroll()]).is_err());
+		assert!(validate(&[instructor_share().boxed(), instructor_share().boxed()]).is_err());
+		assert!(validate(&[instructor2().boxed(), instructor_share().boxed()]).is_ok());
+		assert!(validate(&[hod2pc().boxed(), hod().boxed()]).is_err());
+		assert!(validate(&[hydro_resonance(&[Element::Hydro, Element::Pyro]).boxed()]).is_err());
+		assert!(validate(&[hydro_resonance(&[Element::Hydro, Element::Hydro]).boxed()]).is_ok());
+	}
+}
@@ -1,45 +1,287 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+use std::thread;
+use serde::Serialize;
+
 use crate::*;
+use crate::artifacts::{self, ArtifactBuild, ArtifactStat, Slot};
+use crate::buffs::{ActiveBuff, BoxedBuff};
+use crate::enemies::Enemy;
+use crate::rolls::RollPolicy;
+use crate::weapons::WeaponEntry;
 
-pub fn weapon_calculator(
-    weapons: Vec<(&str, &dyn Fn(CharStats) -> CharStats, Box<dyn Fn(CharStats, CharStats) -> CharStats>)>,
-    arti_mainstat_distributions: Vec<[f32; 6]>,
-    arti_substat_distributions: Vec<[usize; 5]>,
-	relative_to: &str,
-    rotation: fn(&[f32; 6], &[usize; 5], base: &dyn Fn(CharStats) -> CharStats, buff: &Box<dyn Fn(CharStats, CharStats) -> CharStats>) -> f32,
-) {
-    let mut weapons_dpr = Vec::new();
-	for (name, base, buff) in &weapons {
-		let mut max_dpr = 0.0;
-		for mainstats in &arti_mainstat_distributions {
-			for substats in &arti_substat_distributions {
-				let damage = rotation(mainstats, substats, base, buff);
-				if damage > max_dpr {
-					max_dpr = damage;
+// Anything that turns a build and a weapon into damage per rotation,
+// along with the final stats the damage was computed from
+pub trait Rotation: Fn(&ArtifactBuild, &Substats, &RollPolicy, &dyn Fn(CharStats) -> CharStats, &BoxedBuff, &Enemy) -> (f32, CharStats, Vec<ActiveBuff>) {}
+
+impl<T: Fn(&ArtifactBuild, &Substats, &RollPolicy, &dyn Fn(CharStats) -> CharStats, &BoxedBuff, &Enemy) -> (f32, CharStats, Vec<ActiveBuff>)> Rotation for T {}
+
+// The pieces the search settled on and where its rolls went
+#[derive(Clone, Debug, Serialize)]
+pub struct Build {
+	pub artifacts: ArtifactBuild,
+	pub substats: Substats,
+}
+
+// The best a single weapon managed across all builds we tried
+#[derive(Clone, Debug, Serialize)]
+pub struct RankedWeapon {
+	pub name: String,
+	pub dpr: f32,
+	// Only for rotations that know how long they last
+	pub dps: Option<f32>,
+	// ER the build had to reach to burst every rotation
+	pub min_er: Option<f32>,
+	// Artifact set the search picked, if it tried more than one
+	pub set: Option<String>,
+	pub build: Build,
+	pub stats: CharStats,
+	pub buffs: Vec<ActiveBuff>,
+	// Missing if the reference weapon wasn't part of the comparison, or did
+	// no damage (i.e. none of its builds reached the ER)
+	pub of_reference: Option<f32>,
+}
+
+// Weapons sorted from the highest DPR to the lowest
+#[derive(Clone, Debug, Serialize)]
+pub struct WeaponRanking {
+	pub reference: String,
+	// The roll policy the substats followed
+	pub standard: String,
+	pub weapons: Vec<RankedWeapon>,
+}
+
+// Evaluates `f` for every index in 0..count on all available cores.
+// Threads pick up indices one at a time, so uneven jobs still balance out,
+// and the results always come back in index order.
+pub fn parallel_map<T: Send>(
+	count: usize,
+	f: impl Fn(usize) -> T + Sync
+) -> Vec<T> {
+	let threads = thread::available_parallelism()
+		.map(|x| x.get())
+		.unwrap_or(1)
+		.min(count.max(1));
+	let next = AtomicUsize::new(0);
+
+	let mut results: Vec<(usize, T)> = thread::scope(|scope| {
+		let workers: Vec<_> = (0..threads).map(|_| scope.spawn(|| {
+			let mut local = Vec::new();
+			loop {
+				let index = next.fetch_add(1, Ordering::Relaxed);
+				if index >= count {
+					break local;
+				}
+				local.push((index, f(index)));
+			}
+		})).collect();
+
+		workers
+			.into_iter()
+			.flat_map(|x| x.join().unwrap())
+			.collect()
+	});
+
+	results.sort_by_key(|x| x.0);
+	results.into_iter().map(|x| x.1).collect()
+}
+
+// The sands/goblet/circlet layouts we've been investigating so far
+pub fn default_layouts() -> Vec<ArtifactBuild> {
+	use ArtifactStat::*;
+	[
+		// Sands          Goblet        Circlet
+		(Em,              Em,           Em),          // Triple EM
+		(Em,              Em,           CritDamage),  // Double EM + CD
+		(Em,              Em,           CritRate),    // Double EM + CR
+		(Em,              ElementalDmg, CritDamage),  // EM + Dmg + CD
+		(Em,              HpPercent,    CritRate),    // EM + HP + CR
+		(Em,              HpPercent,    CritDamage),  // EM + HP + CD
+		(HpPercent,       ElementalDmg, CritDamage),  // HP + Dmg + CD
+		(HpPercent,       ElementalDmg, CritRate),    // HP + Dmg + CR
+		(Em,              AtkPercent,   CritRate),    // EM + ATK + CR
+		(Em,              AtkPercent,   CritDamage),  // EM + ATK + CD
+		(AtkPercent,      ElementalDmg, CritDamage),  // ATK + Dmg + CD
+		(AtkPercent,      ElementalDmg, CritRate),    // ATK + Dmg + CR
+		(DefPercent,      ElementalDmg, CritRate),    // DEF + Dmg + CR
+		(DefPercent,      ElementalDmg, CritDamage),  // DEF + Dmg + CD
+		(EnergyRecharge,  ElementalDmg, CritRate),    // ER + Dmg + CR
+		(EnergyRecharge,  ElementalDmg, CritDamage),  // ER + Dmg + CD
+	]
+		.into_iter()
+		.map(|(sands, goblet, circlet)| ArtifactBuild::layout(sands, goblet, circlet).unwrap())
+		.collect()
+}
+
+// Mainstats that get any of the setups more damage: the ones doing better in
+// a slot than the worst mainstat that slot can have. That one is usually
+// something the setup doesn't scale with at all, a healing circlet if nothing
+// else. ER counts for setups with an ER to reach.
+pub fn scaling_mainstats(setups: &[Setup], policy: &RollPolicy, enemy: &Enemy) -> [bool; 8] {
+	// The slot's first mainstat everywhere but in `slot`
+	let with = |slot: Slot, stat: ArtifactStat| {
+		let pick = |x: Slot| if x == slot { stat } else { x.main_stats()[0] };
+		ArtifactBuild::layout(pick(Slot::Sands), pick(Slot::Goblet), pick(Slot::Circlet)).unwrap()
+	};
+
+	let mut scaling = [false; 8];
+	for setup in setups {
+		let base = |stats| setup.weapon.weapon.base(stats);
+		let dpr = |build: &ArtifactBuild| (setup.rotation)(build, &[0; 7], policy, &base, &setup.weapon.buff, enemy).0;
+		for slot in [Slot::Sands, Slot::Goblet, Slot::Circlet] {
+			let dprs: Vec<(ArtifactStat, f32)> = slot.main_stats().iter().map(|&x| (x, dpr(&with(slot, x)))).collect();
+			let worst = dprs.iter().map(|x| x.1).fold(f32::INFINITY, f32::min);
+			for (stat, dpr) in dprs {
+				if let Some(i) = stat.mainstat_index() {
+					scaling[i] |= dpr > worst;
 				}
 			}
 		}
-		weapons_dpr.push((name, max_dpr));
+		if setup.min_er.is_some() {
+			scaling[ArtifactStat::EnergyRecharge.mainstat_index().unwrap()] = true;
+		}
 	}
+	scaling
+}
+
+// Every legal layout, but only with mainstats the setups scale with
+pub fn scaling_layouts(setups: &[Setup], policy: &RollPolicy, enemy: &Enemy) -> Vec<ArtifactBuild> {
+	let scaling = scaling_mainstats(setups, policy, enemy);
+	artifacts::layouts(|x| x.mainstat_index().is_some_and(|i| scaling[i]))
+}
+
+// A weapon on the character as the rotation sees it, i.e. "C1 + Sun R1"
+// where the rotation was built for C1
+pub struct Setup<'a> {
+	pub name: String,
+	pub weapon: WeaponEntry,
+	pub rotation: &'a (dyn Rotation + Sync),
+	// How long the rotation lasts in s, if it's timed
+	pub length: Option<f32>,
+	// Builds below this ER don't count, they wouldn't have their burst
+	pub min_er: Option<f32>,
+	// Artifact set the rotation was built with, if it isn't its own. Setups
+	// sharing a name are alternatives and only the best one is ranked.
+	pub set: Option<String>,
+}
+
+// Every weapon brings its own rotation so constellations (or anything else
+// in the config) can be compared too
+pub fn setup_calculator(
+	setups: Vec<Setup>,
+	layouts: Vec<ArtifactBuild>,
+	policy: &RollPolicy,
+	relative_to: &str,
+	enemy: &Enemy,
+) -> WeaponRanking {
+	// Setups sharing a name compete for one spot, so a job only has to reach
+	// the best any of them found so far. Ties still get searched, so which
+	// one wins doesn't depend on which thread got there first. Bits of
+	// non-negative floats compare the same as the floats.
+	let mut names = HashMap::new();
+	let spots: Vec<usize> = setups.iter().map(|x| { let next = names.len(); *names.entry(x.name.as_str()).or_insert(next) }).collect();
+	let floors: Vec<AtomicU32> = (0..names.len()).map(|_| AtomicU32::new(0)).collect();
+
+	// Every setup and layout pair is an independent job
+	let num_layouts = layouts.len();
+	let builds = parallel_map(setups.len() * num_layouts, |job| {
+		let setup = &setups[job / num_layouts];
+		let floor = &floors[spots[job / num_layouts]];
+		let base = |stats| setup.weapon.weapon.base(stats);
+		let layout = &layouts[job % num_layouts];
+		let dpr = |substats: &Substats| {
+			let (dpr, stats, _) = (setup.rotation)(layout, substats, policy, &base, &setup.weapon.buff, enemy);
+			match setup.min_er {
+				Some(x) if stats.energy_recharge < x => 0.0,
+				_ => dpr
+			}
+		};
+		// Placing rolls on pieces can lose some when one more roll goes in,
+		// the per-stat caps the rotation applies on its own never do. They
+		// keep at least as much as the pieces can, so the search stays exact.
+		let found = optimizer::branch_and_bound(
+			policy.rolls(),
+			|substats| dpr(&policy.placed(substats, layout)),
+			dpr,
+			f32::from_bits(floor.load(Ordering::Relaxed))
+		);
+		floor.fetch_max(found.1.max(0.0).to_bits(), Ordering::Relaxed);
+		found
+	});
+
+	let mut ranked = Vec::new();
+	for (i, setup) in setups.iter().enumerate() {
+		let mut best = 0;
+		for j in 0..num_layouts {
+			if builds[i * num_layouts + j].1 > builds[i * num_layouts + best].1 {
+				best = j;
+			}
+		}
+
+		let (rolls, _) = builds[i * num_layouts + best];
+		let artifacts = policy.place(&rolls, &layouts[best]);
+		let substats = policy.placed(&rolls, &layouts[best]);
+		let (mut dpr, stats, buffs) = (setup.rotation)(&artifacts, &substats, policy, &|stats| setup.weapon.weapon.base(stats), &setup.weapon.buff, enemy);
+		if setup.min_er.is_some_and(|x| stats.energy_recharge < x) {
+			dpr = 0.0;
+		}
+		ranked.push(RankedWeapon {
+			name: setup.name.clone(),
+			dpr,
+			dps: setup.length.map(|x| dpr / x),
+			min_er: setup.min_er,
+			set: setup.set.clone(),
+			build: Build { artifacts, substats },
+			stats,
+			buffs,
+			of_reference: None,
+		});
+	}
+
+	ranked.sort_by(|a, b| b.dpr.total_cmp(&a.dpr));
+	let mut seen = std::collections::HashSet::new();
+	ranked.retain(|x| seen.insert(x.name.clone()));
+	if let Some(reference) = ranked.iter().find(|x| x.name == relative_to && x.dpr > 0.0).map(|x| x.dpr) {
+		for weapon in &mut ranked {
+			weapon.of_reference = Some(weapon.dpr / reference * 100.0);
+		}
+	}
+
+	WeaponRanking {
+		reference: relative_to.to_string(),
+		standard: policy.to_string(),
+		weapons: ranked,
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::weapons::{self, Conditions};
 
-	weapons_dpr.sort_by_key(|x| x.1 as usize);
-	weapons_dpr.reverse();
-	let mut r1_damage = 0.0;
-	for (name, dpr) in &weapons_dpr {
-		if **name == relative_to {
-			r1_damage = *dpr;
-			break;
+	fn flat(dpr: f32) -> impl Rotation + Sync {
+		move |_: &ArtifactBuild, _: &Substats, _: &RollPolicy, base: &dyn Fn(CharStats) -> CharStats, _: &BoxedBuff, _: &Enemy| {
+			(dpr, base(characters::FRAUD.base(characters::Level::MAX).unwrap()), Vec::new())
 		}
 	}
 
-	println!(
-		"{0: <23} {1: <13} {2: <10} Comments",
-		"Weapon", "DPR", "% of R1"
-	);
-	for (name, dpr) in weapons_dpr {
-		let of_r1 = (dpr / r1_damage * 100.0) as usize;
-		println!(
-			"{0: <23} {1: <13} {2: <11}",
-			name, dpr as usize, of_r1
+	// A reference that never reaches its ER does no damage, there's nothing to be a % of
+	#[test]
+	fn no_percentages_of_nothing() {
+		let (none, some) = (flat(0.0), flat(100.0));
+		let setup = |name: &str, rotation| Setup {
+			name: name.to_string(),
+			weapon: weapons::by_id("sun").unwrap().at(1, &Conditions::default()).unwrap(),
+			rotation,
+			length: None,
+			min_er: None,
+			set: None,
+		};
+		let layouts = vec![ArtifactBuild::layout(ArtifactStat::AtkPercent, ArtifactStat::ElementalDmg, ArtifactStat::CritRate).unwrap()];
+		let rank = |reference| setup_calculator(
+			vec![setup("None", &none), setup("Some", &some)], layouts.clone(), &RollPolicy::default(), reference, &enemies::MASANORI
 		);
+		assert!(rank("None").weapons.iter().all(|x| x.of_reference.is_none()));
+		assert_eq!(rank("Some").weapons.iter().map(|x| x.of_reference).collect::<Vec<_>>(), [Some(100.0), Some(0.0)]);
 	}
-}
\ No newline at end of file
+}
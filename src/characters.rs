@@ -1,61 +1,496 @@
-use crate::CharStats;
-
-pub const TAO: CharStats = CharStats {
-	hp: 15552.0,
-	atk: 106.43,
-	crit_rate: 5.0,
-	crit_damage: 88.4,
-	dmg_bonus: 0.0,
-	na_bonus: 0.0,
-	na_bonus_flat: 0.0,
-	ca_bonus: 0.0,
-	reaction_bonus: 0.0,
-	skill_bonus: 0.0,
-	em: 0.0,
-	res_shred: 0.0,
+use std::str::FromStr;
+use serde::Deserialize;
+
+use crate::{CharStats, Element, ElementMap};
+use crate::buffs::{self, BoxedBuff, Buff, Source};
+use crate::sets::SetChoice;
+use crate::weapons::WeaponClass;
+
+#[derive(Copy, Clone, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Talent {
+	Normal,
+	Charged,
+	Skill,
+	Burst,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Scaling {
+	Hp,
+	Atk,
+	Def,
+	Em,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum AscensionStat {
+	HpPercent,
+	AtkPercent,
+	DefPercent,
+	Em,
+	CritRate,
+	CritDamage,
+	EnergyRecharge,
+	ElementalBonus,
+	PhysicalBonus,
+	// Healing isn't modelled, it's only there so those characters import
+	HealingBonus,
+}
+
+/////////////////////////////
+///////// Levels ////////////
+/////////////////////////////
+
+// Level cap of every ascension phase
+const LEVEL_CAPS: [usize; 7] = [20, 40, 50, 60, 70, 80, 90];
+
+// Level and ascension phase, 80 and 80+ (ascended but not leveled yet) differ
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Level {
+	pub level: usize,
+	pub ascension: usize,
+}
+
+impl Level {
+	pub const MAX: Level = Level { level: 90, ascension: 6 };
+
+	pub fn new(level: usize, ascension: usize) -> Result<Self, String> {
+		let min = match ascension {
+			0 => 1,
+			1..=6 => LEVEL_CAPS[ascension - 1],
+			_ => return Err(format!("ascension {} is not within 0-6", ascension))
+		};
+		if level < min || level > LEVEL_CAPS[ascension] {
+			return Err(format!("level {} is not within {}-{} at ascension {}", level, min, LEVEL_CAPS[ascension], ascension));
+		}
+		Ok(Level { level, ascension })
+	}
+}
+
+// "80" is a character that reached 80, "80+" one that ascended after that
+impl FromStr for Level {
+	type Err = String;
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let (number, ascended) = match s.strip_suffix('+') {
+			Some(x) => (x, true),
+			None => (s, false)
+		};
+		let level: usize = number.parse()
+			.map_err(|_| format!("expected a level like 80 or 80+, got '{}'", s))?;
+		let ascension = LEVEL_CAPS.iter().position(|x| *x >= level)
+			.filter(|x| *x + (ascended as usize) < LEVEL_CAPS.len())
+			.ok_or(format!("level {} is past the last ascension", s))?;
+		Level::new(level, ascension + ascended as usize)
+	}
+}
+
+// Every 5* character grows along the same curve, so it's kept relative to
+// level 90. These are Hu Tao's HP breakpoints over her level 90 HP.
+const FIVE_STAR_CURVE: [(usize, usize, f32); 14] = [
+	(1, 0, 1211.0 / 15552.0),
+	(20, 0, 3141.0 / 15552.0),
+	(20, 1, 4179.0 / 15552.0),
+	(40, 1, 6253.0 / 15552.0),
+	(40, 2, 6990.0 / 15552.0),
+	(50, 2, 8038.0 / 15552.0),
+	(50, 3, 8999.0 / 15552.0),
+	(60, 3, 10113.0 / 15552.0),
+	(60, 4, 10875.0 / 15552.0),
+	(70, 4, 11997.0 / 15552.0),
+	(70, 5, 12759.0 / 15552.0),
+	(80, 5, 13890.0 / 15552.0),
+	(80, 6, 14652.0 / 15552.0),
+	(90, 6, 1.0),
+];
+
+// Within a phase the curve is close enough to a straight line
+fn curve(level: Level) -> f32 {
+	let mut phase = FIVE_STAR_CURVE.iter().filter(|x| x.1 == level.ascension);
+	let from = phase.next().unwrap();
+	let to = phase.next_back().unwrap_or(from);
+	from.2 + (to.2 - from.2) * (level.level - from.0) as f32 / (to.0 - from.0) as f32
+}
+
+// Quarters of the ascension stat unlocked by each phase
+const ASCENSION_STAT_STEPS: [f32; 7] = [0.0, 0.0, 1.0, 2.0, 2.0, 3.0, 4.0];
+
+/////////////////////////////
+///////// Talents ///////////
+/////////////////////////////
+
+// How elemental talents grow with their level, relative to level 1
+const ELEMENTAL_SCALING: [f32; 15] = [
+	1.0, 1.075, 1.15, 1.25, 1.325, 1.4, 1.5, 1.6, 1.7, 1.8, 1.9, 2.0, 2.125, 2.25, 2.375
+];
+
+// Multipliers (in %) for talent levels 1-15, built off of the level 10 one
+// since that's what everybody quotes
+const fn elemental(at_10: f32) -> [f32; 15] {
+	let mut table = [0.0; 15];
+	let mut i = 0;
+	while i < 15 {
+		table[i] = at_10 * (ELEMENTAL_SCALING[i] / ELEMENTAL_SCALING[9]);
+		i += 1;
+	}
+	table
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Talents {
+	pub normal: usize,
+	pub skill: usize,
+	pub burst: usize,
+}
+
+impl Talents {
+	pub const DEFAULT: Talents = Talents { normal: 10, skill: 10, burst: 10 };
+
+	pub fn level(&self, talent: Talent) -> usize {
+		match talent {
+			Talent::Normal | Talent::Charged => self.normal,
+			Talent::Skill => self.skill,
+			Talent::Burst => self.burst,
+		}
+	}
+}
+
+// "9,10,10" for normal attack, skill and burst
+impl FromStr for Talents {
+	type Err = String;
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let levels: Vec<usize> = s.split(',')
+			.map(|x| x.trim().parse().ok().filter(|x| (1..=15).contains(x)))
+			.collect::<Option<_>>()
+			.ok_or(format!("expected three talent levels within 1-15 like 9,10,10, got '{}'", s))?;
+		match levels[..] {
+			[normal, skill, burst] => Ok(Talents { normal, skill, burst }),
+			_ => Err(format!("expected three talent levels like 9,10,10, got '{}'", s))
+		}
+	}
+}
+
+// A single talent multiplier, `talent` decides which talent level it uses
+pub struct Hit {
+	pub id: &'static str,
+	pub talent: Talent,
+	pub multipliers: [f32; 15],
+}
+
+/////////////////////////////
+/////// Characters //////////
+/////////////////////////////
+
+// How a character is built, what the rotations get evaluated with
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Config {
+	pub level: Level,
+	pub talents: Talents,
+	pub constellation: usize,
+	// Artifact set to wear instead of the one the rotation was written with
+	pub set: Option<SetChoice>,
+}
+
+impl Default for Config {
+	fn default() -> Self {
+		Config { level: Level::MAX, talents: Talents::DEFAULT, constellation: 0, set: None }
+	}
+}
+
+// What a constellation does, the parts we don't model are left out
+pub enum Effect {
+	// +3 levels to a talent, up to 15
+	Talent(Talent),
+	// A buff on the character's own stats
+	Buff(fn() -> BoxedBuff),
+	// Percentage points added to a hit's multiplier
+	Multiplier(&'static str, f32),
+	// A hit that only exists from this constellation on
+	Hit(Hit),
+}
+
+// How base HP, ATK and DEF go up with levels
+pub enum Growth {
+	// Stats at 90, the shared 5* curve takes care of the other levels
+	FiveStar { hp: f32, atk: f32, def: f32 },
+	// Exact values, i.e. out of a data export
+	Table(&'static StatTable),
+}
+
+// Level 1 stats times the level multiplier plus the ascension bonus
+pub struct StatTable {
+	// HP, ATK and DEF in that order everywhere
+	pub base: [f32; 3],
+	pub levels: [[f32; 3]; 90],
+	pub ascension: [[f32; 3]; 7],
+}
+
+pub struct Character {
+	pub id: &'static str,
+	pub name: &'static str,
+	pub element: Element,
+	pub weapon: WeaponClass,
+	pub rarity: usize,
+	pub growth: Growth,
+	// At the last ascension
+	pub ascension_stat: (AscensionStat, f32),
+	// 0 for bursts that don't use energy
+	pub burst_cost: f32,
+	// Per use of the skill, on average
+	pub skill_particles: f32,
+	pub hits: &'static [Hit],
+	// C1 first, constellations past the end don't do anything we model
+	pub constellations: &'static [&'static [Effect]],
+}
+
+impl Character {
+	pub fn base(&self, level: Level) -> Result<CharStats, String> {
+		let [hp, atk, def] = match &self.growth {
+			Growth::FiveStar { hp, atk, def } => {
+				if self.rarity != 5 && level != Level::MAX {
+					return Err(format!("{}: only level 90 stats are known, import the character to use other levels", self.name));
+				}
+				let curve = curve(level);
+				[hp * curve, atk * curve, def * curve]
+			}
+			Growth::Table(table) => {
+				let mut stats = [0.0; 3];
+				for (i, x) in stats.iter_mut().enumerate() {
+					*x = table.base[i] * table.levels[level.level - 1][i] + table.ascension[level.ascension][i];
+				}
+				stats
+			}
+		};
+		let mut stats = CharStats {
+			hp,
+			atk,
+			def,
+			em: 0.0,
+			energy_recharge: 100.0,
+			crit_rate: 5.0,
+			crit_damage: 50.0,
+			dmg_bonus: 0.0,
+			elemental_bonus: ElementMap::ZERO,
+			na_bonus: 0.0,
+			na_bonus_flat: 0.0,
+			skill_bonus: 0.0,
+			ca_bonus: 0.0,
+			reaction_bonus: 0.0,
+			res_shred: ElementMap::ZERO,
+			element: self.element,
+			level: level.level,
+		};
+
+		let (stat, max) = self.ascension_stat;
+		let value = max * ASCENSION_STAT_STEPS[level.ascension] / 4.0;
+		match stat {
+			AscensionStat::HpPercent => stats.hp += stats.hp * value / 100.0,
+			AscensionStat::AtkPercent => stats.atk += stats.atk * value / 100.0,
+			AscensionStat::DefPercent => stats.def += stats.def * value / 100.0,
+			AscensionStat::Em => stats.em += value,
+			AscensionStat::CritRate => stats.crit_rate += value,
+			AscensionStat::CritDamage => stats.crit_damage += value,
+			AscensionStat::EnergyRecharge => stats.energy_recharge += value,
+			AscensionStat::ElementalBonus => stats.elemental_bonus[self.element] += value,
+			AscensionStat::PhysicalBonus => stats.elemental_bonus[Element::Physical] += value,
+			AscensionStat::HealingBonus => (),
+		}
+		Ok(stats)
+	}
+
+	// Everything unlocked at the given constellation
+	pub fn effects(&self, constellation: usize) -> impl Iterator<Item = &Effect> {
+		self.constellations.iter().take(constellation).flat_map(|x| x.iter())
+	}
+
+	// Talent levels after constellations
+	pub fn talents(&self, config: &Config) -> Talents {
+		let mut talents = config.talents;
+		for effect in self.effects(config.constellation) {
+			match effect {
+				Effect::Talent(Talent::Normal | Talent::Charged) => talents.normal = (talents.normal + 3).min(15),
+				Effect::Talent(Talent::Skill) => talents.skill = (talents.skill + 3).min(15),
+				Effect::Talent(Talent::Burst) => talents.burst = (talents.burst + 3).min(15),
+				_ => ()
+			}
+		}
+		talents
+	}
+
+	pub fn buffs(&self, config: &Config) -> Vec<BoxedBuff> {
+		self.effects(config.constellation)
+			.filter_map(|x| match x {
+				Effect::Buff(buff) => Some(buff()),
+				_ => None
+			})
+			.collect()
+	}
+
+	pub fn hit(&self, id: &str, config: &Config) -> Option<&Hit> {
+		let unlocked = self.effects(config.constellation).filter_map(|x| match x {
+			Effect::Hit(hit) => Some(hit),
+			_ => None
+		});
+		self.hits.iter().chain(unlocked).find(|x| x.id == id)
+	}
+
+	// Rotations only ask for hits they know exist, constellation hits
+	// should be checked with `hit` first
+	pub fn multiplier(&self, id: &str, config: &Config) -> f32 {
+		let hit = self.hit(id, config)
+			.unwrap_or_else(|| panic!("{} has no hit '{}' at C{}", self.name, id, config.constellation));
+		let bonus: f32 = self.effects(config.constellation)
+			.filter_map(|x| match x {
+				Effect::Multiplier(hit, bonus) if *hit == id => Some(bonus),
+				_ => None
+			})
+			.sum();
+		hit.multipliers[self.talents(config).level(hit.talent) - 1] + bonus
+	}
+}
+
+pub const TAO: Character = Character {
+	id: "tao",
+	name: "Hu Tao",
+	element: Element::Pyro,
+	weapon: WeaponClass::Polearm,
+	rarity: 5,
+	growth: Growth::FiveStar { hp: 15552.0, atk: 106.43, def: 876.0 },
+	ascension_stat: (AscensionStat::CritDamage, 38.4),
+	burst_cost: 60.0,
+	skill_particles: 5.0,
+	hits: &[],
+	constellations: &[
+		&[],
+		&[],
+		&[Effect::Talent(Talent::Skill)],
+		&[],
+		&[Effect::Talent(Talent::Burst)],
+	],
+};
+
+pub const SHARK: Character = Character {
+	id: "shark",
+	name: "Mualani",
+	element: Element::Hydro,
+	weapon: WeaponClass::Catalyst,
+	rarity: 5,
+	growth: Growth::FiveStar { hp: 15185.0, atk: 182.0, def: 569.0 },
+	ascension_stat: (AscensionStat::CritRate, 19.2),
+	burst_cost: 60.0,
+	skill_particles: 4.0,
+	hits: &[
+		Hit { id: "bite", talent: Talent::Skill, multipliers: elemental(15.6) },
+		Hit { id: "wave_momentum", talent: Talent::Skill, multipliers: elemental(7.8) },
+		Hit { id: "puffer_bonus", talent: Talent::Skill, multipliers: elemental(39.1) },
+		Hit { id: "burst", talent: Talent::Burst, multipliers: elemental(105.2) },
+	],
+	constellations: &[
+		// Once per skill, doesn't scale with talent levels
+		&[Effect::Hit(Hit { id: "c1_bite", talent: Talent::Skill, multipliers: [66.0; 15] })],
+		&[],
+		&[Effect::Talent(Talent::Skill)],
+		&[Effect::Multiplier("burst", 75.0)],
+		&[Effect::Talent(Talent::Burst)],
+	],
+};
+
+pub const EMILIE: Character = Character {
+	id: "emilie",
+	name: "Emilie",
+	element: Element::Dendro,
+	weapon: WeaponClass::Polearm,
+	rarity: 5,
+	growth: Growth::FiveStar { hp: 13568.0, atk: 335.0, def: 730.0 },
+	ascension_stat: (AscensionStat::CritDamage, 38.4),
+	burst_cost: 50.0,
+	skill_particles: 2.5,
+	hits: &[],
+	constellations: &[
+		&[],
+		&[],
+		&[Effect::Talent(Talent::Skill)],
+		&[],
+		&[Effect::Talent(Talent::Burst)],
+	],
 };
 
-pub const SHARK: CharStats =  CharStats {
-	hp: 15185.0,
-	atk: 182.0,
-	crit_rate: 24.2,
-	crit_damage: 50.0,
-	dmg_bonus: 0.0,
-	na_bonus: 0.0,
-	na_bonus_flat: 0.0,
-	ca_bonus: 0.0,
-	reaction_bonus: 0.0,
-	skill_bonus: 0.0,
-	em: 0.0,
-	res_shred: 0.0
+pub const FRAUD: Character = Character {
+	id: "fraud",
+	name: "Mavuika",
+	element: Element::Pyro,
+	weapon: WeaponClass::Claymore,
+	rarity: 5,
+	growth: Growth::FiveStar { hp: 12552.0, atk: 359.0, def: 793.0 },
+	ascension_stat: (AscensionStat::CritDamage, 38.4),
+	// Her burst runs on Fighting Spirit instead
+	burst_cost: 0.0,
+	skill_particles: 5.0,
+	hits: &[
+		Hit { id: "skill_cast", talent: Talent::Skill, multipliers: elemental(133.9) },
+		Hit { id: "skill_tap", talent: Talent::Skill, multipliers: elemental(230.4) },
+	],
+	constellations: &[
+		&[Effect::Buff(|| buffs::named("Mavuika C1", Source::Character, |base, mut stats| {
+			stats.atk += 0.4 * base.atk;
+			stats
+		}).boxed())],
+		// Only the base ATK part, counted as flat ATK
+		&[Effect::Buff(|| buffs::named("Mavuika C2", Source::Character, |_, mut stats| {
+			stats.atk += 200.0;
+			stats
+		}).boxed())],
+		&[Effect::Talent(Talent::Burst)],
+		&[],
+		&[Effect::Talent(Talent::Skill)],
+	],
 };
 
-pub const EMILIE: CharStats = CharStats {
-	hp: 13568.0,
-	atk: 335.0,
-	crit_rate: 5.0,
-	crit_damage: 88.4,
-	dmg_bonus: 0.0,
-	na_bonus: 0.0,
-	na_bonus_flat: 0.0,
-	ca_bonus: 0.0,
-	reaction_bonus: 0.0,
-	skill_bonus: 0.0,
-	em: 0.0,
-	res_shred: 0.0,
+// Only here to support, her burst scales off her DEF
+pub const YUN: Character = Character {
+	id: "yun",
+	name: "Yun Jin",
+	element: Element::Geo,
+	weapon: WeaponClass::Polearm,
+	rarity: 4,
+	growth: Growth::FiveStar { hp: 10657.0, atk: 191.0, def: 734.0 },
+	ascension_stat: (AscensionStat::EnergyRecharge, 26.7),
+	burst_cost: 60.0,
+	skill_particles: 3.0,
+	hits: &[],
+	constellations: &[],
 };
 
-pub const FRAUD: CharStats = CharStats {
-	hp: 12552.0,
-	atk: 359.0,
-	crit_rate: 5.0,
-	crit_damage: 88.4,
-	dmg_bonus: 0.0,
-	na_bonus: 0.0,
-	na_bonus_flat: 0.0,
-	ca_bonus: 0.0,
-	reaction_bonus: 0.0,
-	em: 0.0,
-	res_shred: 0.0,
-	skill_bonus: 0.0
-};
\ No newline at end of file
+pub const CHARACTERS: [&Character; 5] = [&TAO, &SHARK, &EMILIE, &FRAUD, &YUN];
+
+pub fn by_id(id: &str) -> Option<&'static Character> {
+	CHARACTERS.into_iter().find(|x| x.id == id)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn constellations_stack_up() {
+		let c0 = Config::default();
+		let c5 = Config { constellation: 5, ..Config::default() };
+		assert!(SHARK.hit("c1_bite", &c0).is_none());
+		assert_eq!(SHARK.multiplier("c1_bite", &c5), 66.0);
+
+		assert_eq!(SHARK.talents(&c5), Talents { normal: 10, skill: 13, burst: 13 });
+		let burst = SHARK.hit("burst", &c0).unwrap().multipliers[12];
+		assert_eq!(SHARK.multiplier("burst", &c5), burst + 75.0);
+		assert_eq!(FRAUD.buffs(&c5).len(), 2);
+	}
+
+	#[test]
+	fn curve_follows_the_breakpoints() {
+		assert_eq!(curve(Level::MAX), 1.0);
+		assert_eq!(curve("80+".parse().unwrap()), 14652.0 / 15552.0);
+		assert_eq!(curve("1".parse().unwrap()), 1211.0 / 15552.0);
+		let midway = curve("85".parse().unwrap());
+		assert!((midway - (14652.0 + 15552.0) / 2.0 / 15552.0).abs() < 1e-6);
+	}
+}
@@ -0,0 +1,478 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::*;
+use crate::artifacts::ArtifactBuild;
+use crate::calculators::{Rotation, Setup, WeaponRanking};
+use crate::characters::Config;
+use crate::enemies::Enemy;
+use crate::reports::Format;
+use crate::rolls::{self, RollModel, RollPolicy, RollTier};
+use crate::rotations::BoxedRotation;
+use crate::sets::SetChoice;
+use crate::weapons::{Conditions, Weapon, WeaponClass, WeaponEntry};
+
+const USAGE: &str = "\
+usage: genshin-rs <command> [options]
+
+commands:
+  compare-weapons   rank every weapon of a class on a rotation
+  optimize-build    find the best artifacts for a single weapon
+  list-rotations    show built-in rotations and the ones in rotations/
+  list-weapons      show the weapons we know about, built in and in data/
+  import            check the exports in data/ and show what they contain
+
+options:
+  --rotation <name|path>   built-in rotation, or a .toml/.json rotation file
+  --class <class>          sword, claymore, polearm, catalyst or bow (default:
+                           what the rotation's character wields)
+  --refinements <list>     refinements to compare, i.e. 1,5 (default: 1,5)
+  --weapon <name|id>       i.e. \"Sun R1\" or sun (at R1), for optimize-build
+                           (default: the one the rotation file names)
+  --reference <name>       weapon the others are compared to (default: first in the class),
+                           at the first constellation unless it names one
+  --level <level>          character level, i.e. 80 or 80+ once ascended (default: 90)
+  --talents <levels>       normal attack, skill and burst levels (default: 10,10,10)
+  --constellation <n>      0-6 (default: 0), compare-weapons takes a list like 0,1
+                           and ranks every weapon at each, i.e. \"C1 + Sun R1\"
+  --min-er <n|off>         ER every build has to reach, in % (default: what the
+                           rotation's energy model needs to burst every rotation)
+  --layouts <which>        sands/goblet/circlet layouts to try: default (the ones
+                           we picked by hand), all (every legal one) or scaling
+                           (every legal one with stats the rotation scales with)
+  --policy <policy>        how substat rolls turn into stats: kqms (the KQM
+                           standard, the default), raw (uncapped rolls on top of
+                           KQMS' fixed ones) or custom (pieces as in game)
+  --rolls <n>              rolls the search hands out, for raw (default: 20) and
+                           custom (default: 30, out of 40)
+  --roll-tier <tier>       70, 80, 90, 100 or average, for custom (default: average)
+  --flat-rolls <hp,atk,def>  flat rolls every build has, for custom (default: 2,2,1)
+  --sets <which>           artifact sets to try: own (the rotation's, the default)
+                           or all (every 4pc and 2+2 of the catalog, about 5
+                           times slower)
+  --enemy <name>           dummy_90, dummy_100, masanori, ruin_guard (default: masanori)
+  --format <format>        table, csv, json or markdown (default: table)
+  --output <path>          write into a file instead of stdout
+";
+
+const ROTATIONS_DIR: &str = "rotations";
+
+struct Options(HashMap<String, String>);
+
+impl Options {
+	fn parse(args: &[String], allowed: &[&str]) -> Result<Self, String> {
+		let mut options = HashMap::new();
+		let mut args = args.iter();
+		while let Some(arg) = args.next() {
+			let key = arg.strip_prefix("--")
+				.filter(|x| allowed.contains(x))
+				.ok_or(format!("unexpected argument '{}'\n\n{}", arg, USAGE))?;
+			let value = args.next()
+				.ok_or(format!("missing value for '{}'", arg))?;
+			options.insert(key.to_string(), value.clone());
+		}
+		Ok(Options(options))
+	}
+
+	fn get(&self, key: &str) -> Option<&str> {
+		self.0.get(key).map(|x| x.as_str())
+	}
+
+	fn required(&self, key: &str) -> Result<&str, String> {
+		self.get(key).ok_or(format!("missing --{}\n\n{}", key, USAGE))
+	}
+
+	fn rolls(&self) -> Result<Option<usize>, String> {
+		self.get("rolls")
+			.map(|x| x.parse().map_err(|_| format!("--rolls expects a number, got '{}'", x)))
+			.transpose()
+	}
+
+	fn policy(&self) -> Result<RollPolicy, String> {
+		let policy = self.get("policy").unwrap_or("kqms");
+		if policy != "custom" && (self.get("roll-tier").is_some() || self.get("flat-rolls").is_some()) {
+			return Err("--roll-tier and --flat-rolls only go with --policy custom".to_string());
+		}
+		match policy {
+			"kqms" => match self.rolls()? {
+				Some(_) => Err(format!("--rolls doesn't go with --policy kqms, the standard has {} liquid rolls", rolls::KQMS_LIQUID)),
+				None => Ok(RollPolicy::Kqms)
+			},
+			"raw" => Ok(RollPolicy::Raw { rolls: self.rolls()?.unwrap_or(rolls::KQMS_LIQUID) }),
+			"custom" => {
+				let mut model = RollModel::realistic();
+				if let Some(x) = self.rolls()? {
+					model.rolls = x;
+				}
+				model.share = match self.get("roll-tier") {
+					None | Some("average") => model.share,
+					Some(x) => RollTier::ALL.iter()
+						.map(|x| x.share())
+						.find(|share| x == (share * 100.0).round().to_string())
+						.ok_or(format!("--roll-tier expects 70, 80, 90, 100 or average, got '{}'", x))?
+				};
+				if let Some(x) = self.get("flat-rolls") {
+					let flat: Vec<usize> = x.split(',').map(|x| x.trim().parse()).collect::<Result<_, _>>()
+						.map_err(|_| format!("--flat-rolls expects flat HP, ATK and DEF rolls like 2,2,1, got '{}'", x))?;
+					model.flat = flat.try_into()
+						.map_err(|_| format!("--flat-rolls expects flat HP, ATK and DEF rolls like 2,2,1, got '{}'", x))?;
+				}
+				model.check().map_err(|e| format!("--rolls: {}", e))?;
+				Ok(RollPolicy::Custom(model))
+			}
+			x => Err(format!("--policy expects kqms, raw or custom, got '{}'", x))
+		}
+	}
+
+	fn layouts(&self, setups: &[Setup], policy: &RollPolicy, enemy: &Enemy) -> Result<Vec<ArtifactBuild>, String> {
+		match self.get("layouts").unwrap_or("default") {
+			"default" => Ok(calculators::default_layouts()),
+			"all" => Ok(artifacts::layouts(|_| true)),
+			"scaling" => Ok(calculators::scaling_layouts(setups, policy, enemy)),
+			x => Err(format!("--layouts expects default, all or scaling, got '{}'", x))
+		}
+	}
+
+	// None stands for the rotation's own set
+	fn sets(&self) -> Result<Vec<Option<SetChoice>>, String> {
+		match self.get("sets").unwrap_or("own") {
+			"own" => Ok(vec![None]),
+			"all" => Ok(SetChoice::all().into_iter().map(Some).collect()),
+			x => Err(format!("--sets expects own or all, got '{}'", x))
+		}
+	}
+
+	fn enemy(&self) -> Result<Enemy, String> {
+		let name = self.get("enemy").unwrap_or("masanori");
+		enemies::by_name(name)
+			.ok_or(format!("unknown enemy '{}', expected one of {}", name, enemies::NAMES.join(", ")))
+	}
+
+	fn format(&self) -> Result<Format, String> {
+		self.get("format").unwrap_or("table").parse()
+	}
+
+	fn output(&self) -> Option<PathBuf> {
+		self.get("output").map(PathBuf::from)
+	}
+
+	fn class(&self) -> Result<WeaponClass, String> {
+		self.required("class")?.parse()
+	}
+
+	fn refinements(&self) -> Result<Vec<usize>, String> {
+		self.get("refinements").unwrap_or("1,5")
+			.split(',')
+			.map(|x| x.trim().parse().map_err(|_| format!("--refinements expects numbers like 1,5, got '{}'", x)))
+			.collect()
+	}
+
+	// Either the name from the reports or a registry id (built in or in data/) at R1
+	fn weapon(&self) -> Result<WeaponEntry, String> {
+		let name = self.required("weapon")?;
+		if let Some(weapon) = weapons::by_display_name(name) {
+			return Ok(weapon);
+		}
+		let weapon = importer::weapon(name)
+			.map_err(|e| format!("{}, see list-weapons", e))?;
+		importer::weapon_at(weapon, 1)
+	}
+
+	// The class at every refinement asked for, imported weapons included
+	fn weapons(&self, imported: &[&'static Weapon], wielded: WeaponClass) -> Result<Vec<WeaponEntry>, String> {
+		let class = match self.get("class") {
+			Some(_) => self.class()?,
+			None => wielded
+		};
+		let refinements = self.refinements()?;
+		let mut entries = weapons::list(class, &refinements, &Conditions::default())?;
+		for &weapon in imported.iter().filter(|x| x.class == class) {
+			for &refinement in &refinements {
+				entries.push(importer::weapon_at(weapon, refinement)?);
+			}
+		}
+		Ok(entries)
+	}
+
+	fn constellations(&self) -> Result<Vec<usize>, String> {
+		self.get("constellation").unwrap_or("0")
+			.split(',')
+			.map(|x| x.trim().parse().ok().filter(|x| *x <= 6)
+				.ok_or(format!("--constellation expects constellations within 0-6 like 0 or 0,1, got '{}'", x)))
+			.collect()
+	}
+
+	fn config(&self) -> Result<Config, String> {
+		match self.constellations()?[..] {
+			[x] => self.config_at(x),
+			_ => Err("--constellation takes a single constellation here".to_string())
+		}
+	}
+
+	fn config_at(&self, constellation: usize) -> Result<Config, String> {
+		let mut config = Config { constellation, ..Config::default() };
+		if let Some(level) = self.get("level") {
+			config.level = level.parse()?;
+		}
+		if let Some(talents) = self.get("talents") {
+			config.talents = talents.parse()?;
+		}
+		Ok(config)
+	}
+
+	// The energy model's answer unless overridden, "off" drops the requirement
+	fn min_er(&self, solved: Option<f32>) -> Result<Option<f32>, String> {
+		match self.get("min-er") {
+			None => Ok(solved),
+			Some("off") => Ok(None),
+			Some(x) => x.parse().map(Some).map_err(|_| format!("--min-er expects a number or off, got '{}'", x))
+		}
+	}
+
+	// Built-in rotations, then rotations/<name>.toml|json, then a plain path.
+	// A file can't take the name of a built-in, one of them would go unnoticed.
+	fn rotation(&self, config: Config) -> Result<LoadedRotation, String> {
+		let name = self.required("rotation")?;
+		let candidates = [
+			Path::new(ROTATIONS_DIR).join(format!("{}.toml", name)),
+			Path::new(ROTATIONS_DIR).join(format!("{}.json", name)),
+			PathBuf::from(name),
+		];
+		if let Some(builtin) = rotations::by_name(name, config) {
+			let builtin = builtin?;
+			if let Some(file) = candidates[..2].iter().find(|x| x.is_file()) {
+				return Err(format!("'{}' is both a built-in rotation and {}, rename the file", name, file.display()));
+			}
+			return Ok(LoadedRotation {
+				rotation: builtin.rotation,
+				length: Some(builtin.length),
+				min_er: self.min_er(builtin.min_er)?,
+				class: builtin.character.weapon,
+				weapon: None,
+			});
+		}
+
+		let path = candidates.iter()
+			.find(|x| x.is_file())
+			.ok_or(format!("unknown rotation '{}', see list-rotations", name))?;
+		let file = loader::load(path)?;
+		Ok(LoadedRotation {
+			rotation: Box::new(file.rotation(&config)?),
+			length: file.length()?,
+			min_er: self.min_er(file.min_er()?)?,
+			class: importer::character(&file.character)?.weapon,
+			weapon: file.weapon()?,
+		})
+	}
+}
+
+// A rotation along with what the calculators need to know about it
+struct LoadedRotation {
+	rotation: BoxedRotation,
+	// In s, if it's timed
+	length: Option<f32>,
+	min_er: Option<f32>,
+	// What the character wields, the class compared unless --class says otherwise
+	class: WeaponClass,
+	// The weapon a file was written for, optimized unless --weapon says otherwise
+	weapon: Option<WeaponEntry>,
+}
+
+pub fn run(args: &[String]) -> Result<(), String> {
+	let (command, rest) = match args.split_first() {
+		Some((command, rest)) => (command.as_str(), rest),
+		None => return Err(USAGE.to_string())
+	};
+
+	match command {
+		"compare-weapons" => {
+			let options = Options::parse(rest, &["rotation", "class", "refinements", "reference", "level", "talents", "constellation", "min-er", "layouts", "policy", "rolls", "roll-tier", "flat-rolls", "sets", "enemy", "format", "output"])?;
+			let constellations = options.constellations()?;
+			let sets = options.sets()?;
+			let imported = imported_weapons();
+			let mut rotations = Vec::new();
+			for &constellation in &constellations {
+				for &set in &sets {
+					let config = Config { set, ..options.config_at(constellation)? };
+					rotations.push((constellation, set, options.rotation(config)?));
+				}
+			}
+
+			// Weapon names alone unless several constellations are compared,
+			// every set of a weapon competes under the same name
+			let mut setups = Vec::new();
+			for (constellation, set, loaded) in &rotations {
+				for weapon in options.weapons(&imported, loaded.class)? {
+					let name = match constellations.len() {
+						1 => weapon.name.clone(),
+						_ => format!("C{} + {}", constellation, weapon.name)
+					};
+					setups.push(Setup {
+						name,
+						weapon,
+						rotation: &loaded.rotation as &(dyn Rotation + Sync),
+						length: loaded.length,
+						min_er: loaded.min_er,
+						set: set.map(|x| x.to_string()),
+					});
+				}
+			}
+			// A plain weapon name stands for it at the first constellation
+			let reference = match options.get("reference") {
+				Some(x) => [x.to_string(), format!("C{} + {}", constellations[0], x)].into_iter()
+					.find(|name| setups.iter().any(|setup| setup.name == *name))
+					.ok_or(format!("--reference '{}' isn't one of the compared weapons", x))?,
+				None => setups.first().map(|x| x.name.clone()).ok_or("no weapons to compare".to_string())?
+			};
+			let enemy = options.enemy()?;
+			let policy = options.policy()?;
+			let layouts = options.layouts(&setups, &policy, &enemy)?;
+			let ranking = calculators::setup_calculator(setups, layouts, &policy, &reference, &enemy);
+			reports::export(&ranking, options.format()?, options.output().as_deref())
+		}
+		"optimize-build" => {
+			let options = Options::parse(rest, &["rotation", "weapon", "level", "talents", "constellation", "min-er", "layouts", "policy", "rolls", "roll-tier", "flat-rolls", "sets", "enemy", "format", "output"])?;
+			let mut rotations = Vec::new();
+			let mut weapons = Vec::new();
+			for set in options.sets()? {
+				let mut loaded = options.rotation(Config { set, ..options.config()? })?;
+				weapons.push(match options.get("weapon") {
+					Some(_) => options.weapon()?,
+					None => loaded.weapon.take().ok_or("missing --weapon, the rotation doesn't name one".to_string())?
+				});
+				rotations.push((set, loaded));
+			}
+			let name = weapons[0].name.clone();
+			let mut setups = Vec::new();
+			for ((set, loaded), weapon) in rotations.iter().zip(weapons) {
+				setups.push(Setup {
+					name: name.clone(),
+					weapon,
+					rotation: &loaded.rotation as &(dyn Rotation + Sync),
+					length: loaded.length,
+					min_er: loaded.min_er,
+					set: set.map(|x| x.to_string()),
+				});
+			}
+			let enemy = options.enemy()?;
+			let policy = options.policy()?;
+			let layouts = options.layouts(&setups, &policy, &enemy)?;
+			let ranking = calculators::setup_calculator(setups, layouts, &policy, &name, &enemy);
+			export_build(&ranking, options.format()?, options.output().as_deref())
+		}
+		"list-rotations" => {
+			Options::parse(rest, &[])?;
+			for name in rotations::BUILTIN {
+				println!("{}", name);
+			}
+			if let Ok(entries) = fs::read_dir(ROTATIONS_DIR) {
+				let mut files: Vec<PathBuf> = entries.flatten().map(|x| x.path()).collect();
+				files.sort();
+				for path in files {
+					let stem = path.file_stem().and_then(|x| x.to_str()).unwrap_or_default();
+					if rotations::BUILTIN.contains(&stem) {
+						eprintln!("skipping {}: '{}' is a built-in rotation, rename the file", path.display(), stem);
+						continue;
+					}
+					match loader::load(&path) {
+						Ok(file) => println!("{}\t({})", file.name, path.display()),
+						Err(e) => eprintln!("skipping {}", e)
+					}
+				}
+			}
+			Ok(())
+		}
+		"list-weapons" => {
+			let options = Options::parse(rest, &["class"])?;
+			let classes = match options.get("class") {
+				Some(_) => vec![options.class()?],
+				None => WeaponClass::ALL.to_vec()
+			};
+			let imported = imported_weapons();
+			for class in classes {
+				for weapon in weapons::of_class(class).chain(imported.iter().copied().filter(|x| x.class == class)) {
+					let (stat, value) = weapon.substat;
+					println!(
+						"{}\t{}\t{}\t{}*\t{} ATK\t{} {}",
+						class.name(), weapon.id, weapon.name, weapon.rarity, weapon.base_atk, stat, value
+					);
+				}
+			}
+			Ok(())
+		}
+		"import" => {
+			Options::parse(rest, &[])?;
+			import_summary()
+		}
+		"help" | "--help" | "-h" => {
+			print!("{}", USAGE);
+			Ok(())
+		}
+		_ => Err(format!("unknown command '{}'\n\n{}", command, USAGE))
+	}
+}
+
+// Weapons in data/ that aren't built in, broken files are skipped
+fn imported_weapons() -> Vec<&'static Weapon> {
+	let mut imported = Vec::new();
+	for id in importer::ids("weapons").iter().filter(|x| weapons::by_id(x).is_none()) {
+		match importer::weapon(id) {
+			Ok(weapon) => imported.push(weapon),
+			Err(e) => eprintln!("skipping {}", e)
+		}
+	}
+	imported
+}
+
+// Parses every file in data/ so drift shows up before a rotation needs it
+fn import_summary() -> Result<(), String> {
+	let mut errors = 0;
+	for id in importer::ids("characters") {
+		match importer::parse_character(&id, &importer::path("characters", &id)) {
+			Ok(character) => {
+				let hits: Vec<_> = character.hits.iter().map(|x| x.id).collect();
+				println!("character\t{}\t{}\t{:?}\t{}", id, character.name, character.element, hits.join(", "));
+			}
+			Err(e) => { eprintln!("{}", e); errors += 1 }
+		}
+	}
+	for id in importer::ids("weapons") {
+		match importer::parse_weapon(&id, &importer::path("weapons", &id)) {
+			Ok(imported) => {
+				let weapon = imported.weapon;
+				let (stat, value) = weapon.substat;
+				let passive = if imported.passive_modelled { "passive" } else { importer::UNMODELLED };
+				println!("weapon\t{}\t{}\t{} ATK\t{} {}\t{}", id, weapon.name, weapon.base_atk, stat, value, passive);
+				for (i, refinement) in imported.refinements.iter().enumerate() {
+					println!("\tR{}\t{:?}\t{}", i + 1, refinement.params, refinement.desc);
+				}
+			}
+			Err(e) => { eprintln!("{}", e); errors += 1 }
+		}
+	}
+	for id in importer::ids("artifacts") {
+		match importer::parse_artifact(&id, &importer::path("artifacts", &id)) {
+			Ok(set) => {
+				let bonuses = if set.bonuses_modelled { "bonuses" } else { "bonuses not modelled" };
+				println!("artifact\t{}\t{}\t{}", id, set.name, bonuses);
+				for (pieces, bonus) in &set.bonuses {
+					println!("\t{}pc\t{:?}\t{}", pieces, bonus.params, bonus.desc);
+				}
+			}
+			Err(e) => { eprintln!("{}", e); errors += 1 }
+		}
+	}
+	match errors {
+		0 => Ok(()),
+		n => Err(format!("{} file(s) in {} couldn't be imported", n, importer::DATA_DIR))
+	}
+}
+
+// A single weapon reads better as a build sheet than as a ranking
+fn export_build(ranking: &WeaponRanking, format: Format, output: Option<&Path>) -> Result<(), String> {
+	match format {
+		Format::Table => reports::write_output(&reports::build_sheet(&ranking.weapons[0], &ranking.standard), output),
+		_ => reports::export(ranking, format, output)
+	}
+}
@@ -0,0 +1,171 @@
+use crate::*;
+use crate::enemies::Enemy;
+use serde::Deserialize;
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Reaction {
+	#[default]
+	None,
+
+	// Amplifying, multiply the triggering hit
+	ForwardVaporize,
+	ReverseVaporize,
+	ForwardMelt,
+	ReverseMelt,
+
+	// Additive, add flat damage to the triggering hit
+	Aggravate,
+	Spread,
+
+	// Transformative, deal their own damage next to the triggering hit
+	Overloaded,
+	ElectroCharged,
+	Superconduct,
+	Swirl(Element),
+	Shatter,
+	Bloom,
+	Hyperbloom,
+	Burgeon,
+	Burning,
+}
+
+// Character level multiplier used by additive and transformative reactions
+const LEVEL_MULTIPLIER: [f32; 90] = [
+	17.17, 18.54, 19.90, 21.27, 22.80, 24.65, 26.64, 28.87, 31.37, 34.14,
+	37.20, 40.66, 44.45, 48.56, 53.75, 59.08, 64.42, 69.72, 75.12, 80.58,
+	86.11, 91.70, 97.24, 102.81, 108.41, 113.20, 118.10, 122.98, 129.73, 136.29,
+	142.67, 149.03, 155.42, 161.83, 169.11, 176.52, 184.07, 191.71, 199.56, 207.38,
+	215.40, 224.17, 233.50, 243.35, 256.06, 268.54, 281.53, 295.01, 309.07, 323.60,
+	336.76, 350.53, 364.48, 378.62, 398.60, 416.40, 434.39, 452.95, 472.61, 492.88,
+	513.57, 539.10, 565.51, 592.54, 624.44, 651.47, 679.50, 707.79, 736.67, 765.64,
+	794.77, 824.68, 851.16, 877.74, 914.23, 946.75, 979.41, 1011.22, 1044.79, 1077.44,
+	1109.00, 1142.98, 1176.37, 1210.18, 1253.84, 1288.95, 1325.48, 1363.46, 1405.10, 1446.85,
+];
+
+pub fn level_multiplier(level: usize) -> f32 {
+	LEVEL_MULTIPLIER[level.clamp(1, 90) - 1]
+}
+
+// The damage formula for a single hit. Talent specific DMG% (normal attack
+// bonus, skill bonus, ...) is passed in, the rest is read off the stats.
+pub fn damage(
+	stats: &CharStats,
+	element: Element,
+	base_dmg: f32,
+	additive_dmg_bonus: f32,
+	talent_bonus: f32,
+	reaction: Reaction,
+	enemy: &Enemy
+) -> f32 {
+	// Effective crit multiplier evaluated as n - number of hits, approaches infinity
+	let crit = 1.0 + (stats.crit_rate / 100.0).clamp(0.0, 1.0) * stats.crit_damage / 100.0;
+	let dmg_bonus = (stats.dmg_bonus_for(element) + talent_bonus) / 100.0;
+
+	let enemy_def_multiplier = enemy.def_multiplier(stats.level);
+	let enemy_res_multiplier = enemy.res_multiplier(element, stats.res_shred[element]);
+
+	let hit = (base_dmg + additive_dmg_bonus + additive_reaction_bonus(reaction, stats)) *
+		(1.0 + dmg_bonus) * crit * enemy_def_multiplier *
+		enemy_res_multiplier * amplifying_multiplier(reaction, stats);
+
+	hit + transformative_damage(reaction, stats, enemy)
+}
+
+pub fn amplifying_multiplier(
+	reaction: Reaction,
+	trigger: &CharStats
+) -> f32 {
+	let base = match reaction {
+		Reaction::ForwardVaporize | Reaction::ForwardMelt => 2.0,
+		Reaction::ReverseVaporize | Reaction::ReverseMelt => 1.5,
+		_ => return 1.0
+	};
+	base * (1.0 + (2.78 * trigger.em) / (1400.0 + trigger.em) + trigger.reaction_bonus / 100.0)
+}
+
+pub fn additive_reaction_bonus(
+	reaction: Reaction,
+	trigger: &CharStats
+) -> f32 {
+	let base = match reaction {
+		Reaction::Aggravate => 1.15,
+		Reaction::Spread => 1.25,
+		_ => return 0.0
+	};
+	base * level_multiplier(trigger.level) *
+	(1.0 + (5.0 * trigger.em) / (1200.0 + trigger.em) + trigger.reaction_bonus / 100.0)
+}
+
+// Transformative reactions can't crit and ignore DEF,
+// but still get hit by the RES of their own element
+pub fn transformative_damage(
+	reaction: Reaction,
+	trigger: &CharStats,
+	enemy: &Enemy
+) -> f32 {
+	let (base, element) = match reaction {
+		Reaction::Overloaded => (2.75, Element::Pyro),
+		Reaction::ElectroCharged => (2.0, Element::Electro),
+		Reaction::Superconduct => (1.5, Element::Cryo),
+		Reaction::Swirl(element) => (0.6, element),
+		Reaction::Shatter => (3.0, Element::Physical),
+		Reaction::Bloom => (2.0, Element::Dendro),
+		Reaction::Hyperbloom => (3.0, Element::Dendro),
+		Reaction::Burgeon => (3.0, Element::Dendro),
+		Reaction::Burning => (0.25, Element::Pyro),
+		_ => return 0.0
+	};
+	base * level_multiplier(trigger.level) *
+	(1.0 + (16.0 * trigger.em) / (2000.0 + trigger.em) + trigger.reaction_bonus / 100.0) *
+	enemy.res_multiplier(element, trigger.res_shred[element])
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::characters::{self, Level};
+	use crate::enemies;
+
+	fn stats(em: f32) -> CharStats {
+		let mut stats = characters::FRAUD.base(Level::MAX).unwrap();
+		stats.em = em;
+		stats
+	}
+
+	#[test]
+	fn amplifying() {
+		assert_eq!(amplifying_multiplier(Reaction::ForwardVaporize, &stats(0.0)), 2.0);
+		assert_eq!(amplifying_multiplier(Reaction::ReverseMelt, &stats(0.0)), 1.5);
+		assert_eq!(amplifying_multiplier(Reaction::Aggravate, &stats(1000.0)), 1.0);
+		// 2.78 * 1000 / 2400 from EM
+		assert!((amplifying_multiplier(Reaction::ForwardMelt, &stats(1000.0)) - 4.316667).abs() < 1e-4);
+		assert!((amplifying_multiplier(Reaction::ReverseVaporize, &stats(1000.0)) - 3.2375).abs() < 1e-4);
+	}
+
+	#[test]
+	fn additive() {
+		// Level 90 multiplier 1446.85
+		assert!((additive_reaction_bonus(Reaction::Aggravate, &stats(0.0)) - 1663.8775).abs() < 1e-2);
+		assert!((additive_reaction_bonus(Reaction::Spread, &stats(0.0)) - 1808.5625).abs() < 1e-2);
+		assert!((additive_reaction_bonus(Reaction::Spread, &stats(200.0)) - 1808.5625 * (1.0 + 1000.0 / 1400.0)).abs() < 1e-2);
+		assert_eq!(additive_reaction_bonus(Reaction::ForwardVaporize, &stats(200.0)), 0.0);
+	}
+
+	#[test]
+	fn transformative() {
+		let enemy = enemies::DUMMY_90;
+		assert!((transformative_damage(Reaction::Overloaded, &stats(0.0), &enemy) - 2.75 * 1446.85 * 0.9).abs() < 1e-2);
+		assert!((transformative_damage(Reaction::Swirl(Element::Hydro), &stats(0.0), &enemy) - 0.6 * 1446.85 * 0.9).abs() < 1e-2);
+		// 16 * 100 / 2100 from EM
+		let hyperbloom = 3.0 * 1446.85 * (1.0 + 1600.0 / 2100.0) * 0.9;
+		assert!((transformative_damage(Reaction::Hyperbloom, &stats(100.0), &enemy) - hyperbloom).abs() < 1e-2);
+
+		// Neither crits nor DEF change it
+		let mut crits = stats(100.0);
+		crits.crit_rate = 100.0;
+		crits.crit_damage = 200.0;
+		let hit = damage(&crits, Element::Dendro, 0.0, 0.0, 0.0, Reaction::Hyperbloom, &enemies::MASANORI);
+		assert!((hit - hyperbloom).abs() < 1e-2);
+	}
+}
@@ -0,0 +1,102 @@
+use crate::{Element, ElementMap};
+
+// Everything we hit is described by its DEF and RES, both of which follow
+// from its level. RES is stored in percentages, indexed by `Element`.
+#[derive(Copy, Clone)]
+pub struct Enemy {
+	pub def: f32,
+	pub res: ElementMap,
+	pub def_reduction: f32,
+	pub def_ignore: f32,
+}
+
+impl Enemy {
+	// Every enemy in the game follows the same DEF curve
+	pub const fn new(level: usize, res: [f32; 8]) -> Self {
+		Self {
+			def: 5.0 * level as f32 + 500.0,
+			res: ElementMap(res),
+			def_reduction: 0.0,
+			def_ignore: 0.0,
+		}
+	}
+
+	pub const fn uniform(level: usize, res: f32) -> Self {
+		Self::new(level, [res; 8])
+	}
+
+	pub fn def_multiplier(&self, char_level: usize) -> f32 {
+		let char_def = 5.0 * char_level as f32 + 500.0;
+		let enemy_def = self.def * (1.0 - self.def_reduction / 100.0) * (1.0 - self.def_ignore / 100.0);
+		char_def / (char_def + enemy_def)
+	}
+
+	// The standard three-branch RES formula. Negative RES is halved,
+	// and anything above 75% gets diminishing returns.
+	pub fn res_multiplier(&self, element: Element, res_shred: f32) -> f32 {
+		let res = (self.res[element] - res_shred) / 100.0;
+		if res < 0.0 {
+			1.0 - res / 2.0
+		} else if res < 0.75 {
+			1.0 - res
+		} else {
+			1.0 / (4.0 * res + 1.0)
+		}
+	}
+}
+
+// The KQMS reference target
+pub const DUMMY_90: Enemy = Enemy::uniform(90, 10.0);
+
+// Abyss floor 12 enemies
+pub const DUMMY_100: Enemy = Enemy::uniform(100, 10.0);
+
+// Lvl. 90 boss we've been testing against since forever. Our numbers have
+// always used a flat 0.48 DEF multiplier for it, a bit below the 0.5 of the
+// DEF curve, so it gets the DEF that gives a level 90 character exactly that.
+pub const MASANORI: Enemy = Enemy { def: 950.0 / 0.48 - 950.0, ..Enemy::uniform(90, 10.0) };
+
+pub const RUIN_GUARD: Enemy = Enemy::new(
+	90,
+	// Pyro Hydro Electro Cryo  Anemo Geo   Dendro Physical
+	[  10.0, 10.0, 10.0,   10.0, 10.0, 10.0, 10.0,  70.0  ]
+);
+
+pub const NAMES: [&str; 4] = ["dummy_90", "dummy_100", "masanori", "ruin_guard"];
+
+pub fn by_name(name: &str) -> Option<Enemy> {
+	match name {
+		"dummy_90" => Some(DUMMY_90),
+		"dummy_100" => Some(DUMMY_100),
+		"masanori" => Some(MASANORI),
+		"ruin_guard" => Some(RUIN_GUARD),
+		_ => None
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn def_multiplier() {
+		assert!((DUMMY_90.def_multiplier(90) - 0.5).abs() < 1e-6);
+		assert!((DUMMY_90.def_multiplier(80) - 900.0 / 1850.0).abs() < 1e-6);
+		assert!((MASANORI.def_multiplier(90) - 0.48).abs() < 1e-6);
+		let shredded = Enemy { def_reduction: 20.0, def_ignore: 10.0, ..DUMMY_90 };
+		assert!((shredded.def_multiplier(90) - 950.0 / (950.0 + 950.0 * 0.8 * 0.9)).abs() < 1e-6);
+	}
+
+	#[test]
+	fn res_multiplier_branches() {
+		// 0-75%
+		assert!((DUMMY_90.res_multiplier(Element::Pyro, 0.0) - 0.9).abs() < 1e-6);
+		// Negative RES is halved
+		assert!((DUMMY_90.res_multiplier(Element::Pyro, 30.0) - 1.1).abs() < 1e-6);
+		assert!((RUIN_GUARD.res_multiplier(Element::Physical, 0.0) - 0.3).abs() < 1e-6);
+		// Above 75%
+		let resistant = Enemy::uniform(90, 80.0);
+		assert!((resistant.res_multiplier(Element::Hydro, 0.0) - 1.0 / 4.2).abs() < 1e-6);
+		assert!((resistant.res_multiplier(Element::Hydro, 80.0) - 1.0).abs() < 1e-6);
+	}
+}
@@ -0,0 +1,92 @@
+use serde::Deserialize;
+
+use crate::*;
+use crate::characters::Character;
+
+// Particles hand out 3 energy when they match the character's element, 2
+// when they're clear and 1 otherwise. Orbs are worth 3 particles, and
+// whoever is off field only gets 60% of it.
+const SAME_ELEMENT: f32 = 3.0;
+const CLEAR: f32 = 2.0;
+const OTHER_ELEMENT: f32 = 1.0;
+const OFF_FIELD: f32 = 0.6;
+
+// Particles the character catches over a rotation, i.e. `{ count = 4, element = "hydro" }`
+#[derive(Clone, Debug, Deserialize)]
+pub struct Particles {
+	pub count: f32,
+	// Clear particles when missing
+	pub element: Option<Element>,
+	#[serde(default)]
+	pub off_field: bool,
+}
+
+impl Particles {
+	// Energy at 100% ER
+	pub fn energy(&self, element: Element) -> f32 {
+		let value = match self.element {
+			Some(x) if x == element => SAME_ELEMENT,
+			Some(_) => OTHER_ELEMENT,
+			None => CLEAR,
+		};
+		self.count * value * if self.off_field { OFF_FIELD } else { 1.0 }
+	}
+}
+
+// Everything that refills the character's burst over one rotation
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct Energy {
+	// Times the character uses their own skill
+	#[serde(default)]
+	pub skills: f32,
+	// From teammates, enemies dropping particles and so on
+	#[serde(default)]
+	pub particles: Vec<Particles>,
+	// Energy that ER doesn't scale, i.e. Favonius or constellations
+	#[serde(default)]
+	pub flat: f32,
+}
+
+impl Energy {
+	// Lowest ER in % that gets the burst back every rotation, None when
+	// the burst doesn't use energy
+	pub fn min_er(&self, character: &Character) -> Result<Option<f32>, String> {
+		if character.burst_cost == 0.0 {
+			return Ok(None);
+		}
+		let own = Particles { count: self.skills * character.skill_particles, element: Some(character.element), off_field: false };
+		let scaled: f32 = self.particles.iter().chain([&own]).map(|x| x.energy(character.element)).sum();
+		let missing = character.burst_cost - self.flat;
+		match (missing, scaled) {
+			(x, _) if x <= 0.0 => Ok(Some(100.0)),
+			(x, y) if y > 0.0 => Ok(Some((x / y * 100.0).max(100.0))),
+			(x, _) => Err(format!(
+				"{} never gets their burst back, {} energy short without any particles",
+				character.name, x
+			))
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn solves_for_er() {
+		// 1 skill (4 hydro particles) and 3 clear particles off field:
+		// 4 * 3 + 3 * 2 * 0.6 = 15.6 energy at 100%, so 60 / 15.6
+		let energy = Energy {
+			skills: 1.0,
+			particles: vec![Particles { count: 3.0, element: None, off_field: true }],
+			flat: 0.0,
+		};
+		let er = energy.min_er(&characters::SHARK).unwrap().unwrap();
+		assert!((er - 60.0 / 15.6 * 100.0).abs() < 1e-3);
+
+		let flat = Energy { flat: 60.0, ..Default::default() };
+		assert_eq!(flat.min_er(&characters::SHARK), Ok(Some(100.0)));
+		assert!(Energy::default().min_er(&characters::SHARK).is_err());
+		assert_eq!(Energy::default().min_er(&characters::FRAUD), Ok(None));
+	}
+}
@@ -0,0 +1,170 @@
+use std::collections::HashMap;
+
+use crate::*;
+use crate::damage::Reaction;
+
+// Element application: which hits actually get to react, given the aura
+// the rest of the team keeps up. Only the amplifying reactions are worked
+// out, anything else leaves the aura as it is.
+
+// Standard ICD, the 1st, 4th, 7th... hit of a sequence applies, and the
+// sequence starts over 2.5s after it started
+const ICD_HITS: usize = 3;
+const ICD_TIMER: f32 = 2.5;
+
+// Hits sharing an ICD tag share a counter, usually one per talent
+#[derive(Default)]
+pub struct Icds(HashMap<String, (f32, usize)>);
+
+impl Icds {
+	pub fn applies(&mut self, tag: &str, time: f32) -> bool {
+		match self.0.get_mut(tag) {
+			Some((start, count)) if time < *start + ICD_TIMER => {
+				let applies = *count % ICD_HITS == 0;
+				*count += 1;
+				applies
+			}
+			_ => {
+				self.0.insert(tag.to_string(), (time, 1));
+				true
+			}
+		}
+	}
+}
+
+struct Aura {
+	element: Element,
+	gauge: f32,
+	// Units per second
+	decay: f32,
+}
+
+#[derive(Default)]
+pub struct Gauge {
+	aura: Option<Aura>,
+	time: f32,
+}
+
+impl Gauge {
+	#[cfg(test)]
+	pub fn aura(&self) -> Option<(Element, f32)> {
+		self.aura.as_ref().map(|x| (x.element, x.gauge))
+	}
+
+	fn advance(&mut self, time: f32) {
+		if let Some(aura) = &mut self.aura {
+			aura.gauge -= aura.decay * (time - self.time).max(0.0);
+			if aura.gauge <= 0.0 {
+				self.aura = None;
+			}
+		}
+		self.time = self.time.max(time);
+	}
+
+	// Applies `units` of the element, returns the reaction it triggered
+	pub fn apply(&mut self, time: f32, element: Element, units: f32) -> Reaction {
+		self.advance(time);
+		let Some(aura) = &mut self.aura else {
+			// Anemo, Geo and physical don't stick around
+			if !matches!(element, Element::Anemo | Element::Geo | Element::Physical) {
+				// An aura is 80% of what's applied and lasts 2.5s per unit plus 7s
+				self.aura = Some(Aura { element, gauge: 0.8 * units, decay: 0.8 * units / (2.5 * units + 7.0) });
+			}
+			return Reaction::None;
+		};
+
+		let (reaction, consumed) = match (aura.element, element) {
+			(x, y) if x == y => {
+				aura.gauge = aura.gauge.max(0.8 * units);
+				return Reaction::None;
+			}
+			(Element::Pyro, Element::Hydro) => (Reaction::ForwardVaporize, 2.0),
+			(Element::Hydro, Element::Pyro) => (Reaction::ReverseVaporize, 0.5),
+			(Element::Cryo, Element::Pyro) => (Reaction::ForwardMelt, 2.0),
+			(Element::Pyro, Element::Cryo) => (Reaction::ReverseMelt, 0.5),
+			_ => return Reaction::None
+		};
+		aura.gauge -= consumed * units;
+		if aura.gauge <= 0.0 {
+			self.aura = None;
+		}
+		reaction
+	}
+}
+
+// A hit or a teammate's application. Teammates are assumed to be past
+// their own ICD, so they go without a tag.
+pub struct Event {
+	pub time: f32,
+	pub element: Element,
+	pub units: f32,
+	pub icd: Option<String>,
+}
+
+// A teammate applying their element from `start`, then every `every` s up
+// to `end`, i.e. Furina's salon members
+pub fn repeated(element: Element, units: f32, start: f32, every: f32, end: f32) -> Vec<Event> {
+	assert!(every > 0.0);
+	let mut events = Vec::new();
+	let mut time = start;
+	while time <= end {
+		events.push(Event { time, element, units, icd: None });
+		time += every;
+	}
+	events
+}
+
+// The reaction each event triggered, in the order they were given. Events
+// at the same time go in that order too.
+pub fn simulate(events: &[Event]) -> Vec<Reaction> {
+	let mut order: Vec<usize> = (0..events.len()).collect();
+	order.sort_by(|a, b| events[*a].time.total_cmp(&events[*b].time));
+
+	let mut gauge = Gauge::default();
+	let mut icds = Icds::default();
+	let mut reactions = vec![Reaction::None; events.len()];
+	for i in order {
+		let event = &events[i];
+		let applies = match &event.icd {
+			Some(tag) => icds.applies(tag, event.time),
+			None => true
+		};
+		if applies {
+			reactions[i] = gauge.apply(event.time, event.element, event.units);
+		}
+	}
+	reactions
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn standard_icd() {
+		let mut icds = Icds::default();
+		let applied: Vec<bool> = [0.0, 0.5, 1.0, 1.5, 2.0, 2.5, 2.6].iter().map(|x| icds.applies("normal", *x)).collect();
+		assert_eq!(applied, [true, false, false, true, false, true, false]);
+		assert!(icds.applies("skill", 2.6));
+	}
+
+	#[test]
+	fn aura_decays_and_gets_consumed() {
+		let mut gauge = Gauge::default();
+		assert_eq!(gauge.apply(0.0, Element::Hydro, 1.0), Reaction::None);
+		// 0.8U of hydro lasts 9.5s
+		gauge.advance(4.75);
+		assert!((gauge.aura().unwrap().1 - 0.4).abs() < 1e-5);
+		assert_eq!(gauge.apply(4.75, Element::Pyro, 1.0), Reaction::ReverseVaporize);
+		assert!(gauge.aura().is_none());
+
+		let reactions = simulate(&[
+			Event { time: 0.0, element: Element::Hydro, units: 1.0, icd: None },
+			Event { time: 0.1, element: Element::Pyro, units: 1.0, icd: Some("skill".to_string()) },
+			Event { time: 0.2, element: Element::Pyro, units: 1.0, icd: Some("skill".to_string()) },
+			Event { time: 9.0, element: Element::Pyro, units: 1.0, icd: Some("skill".to_string()) },
+		]);
+		// The second hit is on ICD, the last one is after hydro ran out
+		assert_eq!(reactions, [Reaction::None, Reaction::ReverseVaporize, Reaction::None, Reaction::None]);
+	}
+}
@@ -0,0 +1,646 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use serde::Deserialize;
+use serde::de::DeserializeOwned;
+
+use crate::*;
+use crate::buffs::MagicBoxed;
+use crate::characters::{AscensionStat, Character, Growth, Hit, StatTable, Talent};
+use crate::weapons::{Conditions, Weapon, WeaponClass, WeaponEntry, WeaponStat};
+
+// Exports saved from the datamine site, one file per id:
+// data/characters/<id>.json, data/weapons/<id>.json and data/artifacts/<id>.json
+pub const DATA_DIR: &str = "data";
+
+// What imported weapons are labelled with when their passive is left out
+pub const UNMODELLED: &str = "passive not modelled";
+
+/////////////////////////////
+////////// Exports //////////
+/////////////////////////////
+
+// Multipliers on the level 1 value, keyed by level ("1" to "90")
+type LevelCurve = HashMap<String, f32>;
+
+// FIGHT_PROP_* to value, one map per ascension phase
+type AscensionProps = Vec<HashMap<String, f32>>;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct CharacterExport {
+	name: String,
+	rarity: String,
+	weapon: String,
+	element: String,
+	#[serde(rename = "BaseHP")]
+	base_hp: f32,
+	#[serde(rename = "BaseATK")]
+	base_atk: f32,
+	#[serde(rename = "BaseDEF")]
+	base_def: f32,
+	stats_modifier: CharacterCurves,
+	skills: Vec<SkillExport>,
+}
+
+#[derive(Deserialize)]
+struct CharacterCurves {
+	#[serde(rename = "HP")]
+	hp: LevelCurve,
+	#[serde(rename = "ATK")]
+	atk: LevelCurve,
+	#[serde(rename = "DEF")]
+	def: LevelCurve,
+	#[serde(rename = "Ascension")]
+	ascension: AscensionProps,
+}
+
+// Normal attack, skill and burst in that order
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct SkillExport {
+	name: String,
+	promote: HashMap<String, PromoteExport>,
+}
+
+// One talent level, `desc` lines look like "Bite DMG|{param1:F1P} Max HP"
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct PromoteExport {
+	level: usize,
+	desc: Vec<String>,
+	param: Vec<f32>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct WeaponExport {
+	name: String,
+	rarity: usize,
+	weapon_type: String,
+	#[serde(rename = "BaseATK")]
+	base_atk: f32,
+	sub_stat: Option<SubStatExport>,
+	stats_modifier: WeaponCurves,
+	// Keyed by refinement ("1" to "5"), missing for weapons without a passive
+	#[serde(default)]
+	refinement: HashMap<String, DescribedExport>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct SubStatExport {
+	prop: String,
+	base: f32,
+}
+
+#[derive(Deserialize)]
+struct WeaponCurves {
+	#[serde(rename = "ATK")]
+	atk: LevelCurve,
+	#[serde(rename = "SubStat", default)]
+	substat: Option<LevelCurve>,
+	#[serde(rename = "Ascension")]
+	ascension: AscensionProps,
+}
+
+// A passive at one refinement or a set bonus, "ATK +{0}%" with `ParamList` [0.2]
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct DescribedExport {
+	desc: String,
+	param_list: Vec<f32>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ArtifactExport {
+	name: String,
+	// The 2pc bonus then the 4pc one
+	affix: Vec<DescribedExport>,
+}
+
+/////////////////////////////
+////////// Entries //////////
+/////////////////////////////
+
+// What a passive or set bonus does as the game describes it, `{0}` in the
+// description is `params[0]`
+pub struct Described {
+	pub desc: String,
+	pub params: Vec<f32>,
+}
+
+// The stats are in the registry entry, the passive is code so it's only
+// kept as parameters unless the weapon is one we already have a passive for
+pub struct ImportedWeapon {
+	pub weapon: &'static Weapon,
+	pub passive_modelled: bool,
+	// R1 to R5, empty for weapons without a passive
+	pub refinements: Vec<Described>,
+}
+
+// Set bonuses are code as well, only the sets of the catalog have them modelled
+pub struct ImportedSet {
+	pub name: String,
+	pub bonuses_modelled: bool,
+	// Keyed by the number of pieces
+	pub bonuses: Vec<(usize, Described)>,
+}
+
+/////////////////////////////
+////////// Parsing //////////
+/////////////////////////////
+
+fn read<T: DeserializeOwned>(path: &Path, kind: &str) -> Result<T, String> {
+	let text = fs::read_to_string(path)
+		.map_err(|e| format!("{}: {}", path.display(), e))?;
+	serde_json::from_str(&text)
+		.map_err(|e| format!("{}: doesn't look like a {} export, the format may have changed ({})", path.display(), kind, e))
+}
+
+fn leak(s: String) -> &'static str {
+	Box::leak(s.into_boxed_str())
+}
+
+fn at_level(curve: &LevelCurve, level: usize, what: &str) -> Result<f32, String> {
+	curve.get(&level.to_string()).copied()
+		.ok_or(format!("{} is missing level {}", what, level))
+}
+
+fn phase(ascension: &AscensionProps, phase: usize) -> Result<&HashMap<String, f32>, String> {
+	match ascension.len() {
+		7 => Ok(&ascension[phase]),
+		n => Err(format!("StatsModifier.Ascension should have 7 phases, found {}", n))
+	}
+}
+
+fn prop(phase: &HashMap<String, f32>, key: &str) -> f32 {
+	phase.get(key).copied().unwrap_or(0.0)
+}
+
+fn element(name: &str) -> Result<Element, String> {
+	Ok(match name {
+		"Fire" => Element::Pyro,
+		"Water" => Element::Hydro,
+		"Electric" => Element::Electro,
+		"Ice" => Element::Cryo,
+		"Wind" => Element::Anemo,
+		"Rock" => Element::Geo,
+		"Grass" => Element::Dendro,
+		_ => return Err(format!("unknown element '{}'", name))
+	})
+}
+
+fn weapon_class(name: &str) -> Result<WeaponClass, String> {
+	Ok(match name {
+		"WEAPON_SWORD_ONE_HAND" => WeaponClass::Sword,
+		"WEAPON_CLAYMORE" => WeaponClass::Claymore,
+		"WEAPON_POLE" => WeaponClass::Polearm,
+		"WEAPON_CATALYST" => WeaponClass::Catalyst,
+		"WEAPON_BOW" => WeaponClass::Bow,
+		_ => return Err(format!("unknown weapon type '{}'", name))
+	})
+}
+
+// Percentages come as fractions
+fn ascension_stat(key: &str, value: f32) -> Option<(AscensionStat, f32)> {
+	Some(match key {
+		"FIGHT_PROP_HP_PERCENT" => (AscensionStat::HpPercent, value * 100.0),
+		"FIGHT_PROP_ATTACK_PERCENT" => (AscensionStat::AtkPercent, value * 100.0),
+		"FIGHT_PROP_DEFENSE_PERCENT" => (AscensionStat::DefPercent, value * 100.0),
+		"FIGHT_PROP_ELEMENT_MASTERY" => (AscensionStat::Em, value),
+		"FIGHT_PROP_CRITICAL" => (AscensionStat::CritRate, value * 100.0),
+		"FIGHT_PROP_CRITICAL_HURT" => (AscensionStat::CritDamage, value * 100.0),
+		"FIGHT_PROP_CHARGE_EFFICIENCY" => (AscensionStat::EnergyRecharge, value * 100.0),
+		"FIGHT_PROP_HEAL_ADD" => (AscensionStat::HealingBonus, value * 100.0),
+		"FIGHT_PROP_PHYSICAL_ADD_HURT" => (AscensionStat::PhysicalBonus, value * 100.0),
+		x if x.ends_with("_ADD_HURT") => (AscensionStat::ElementalBonus, value * 100.0),
+		_ => return None
+	})
+}
+
+fn weapon_stat(key: &str, value: f32) -> Result<(WeaponStat, f32), String> {
+	Ok(match key {
+		"FIGHT_PROP_HP_PERCENT" => (WeaponStat::HpPercent, value * 100.0),
+		"FIGHT_PROP_ATTACK_PERCENT" => (WeaponStat::AtkPercent, value * 100.0),
+		"FIGHT_PROP_DEFENSE_PERCENT" => (WeaponStat::DefPercent, value * 100.0),
+		"FIGHT_PROP_ELEMENT_MASTERY" => (WeaponStat::Em, value),
+		"FIGHT_PROP_CRITICAL" => (WeaponStat::CritRate, value * 100.0),
+		"FIGHT_PROP_CRITICAL_HURT" => (WeaponStat::CritDamage, value * 100.0),
+		"FIGHT_PROP_CHARGE_EFFICIENCY" => (WeaponStat::EnergyRecharge, value * 100.0),
+		_ => return Err(format!("unsupported substat '{}'", key))
+	})
+}
+
+// Every `{0}` in a description needs a param to stand for
+fn described(export: &DescribedExport, what: &str) -> Result<Described, String> {
+	let placeholders = export.desc.split('{')
+		.skip(1)
+		.filter_map(|x| x.split('}').next()?.parse::<usize>().ok());
+	for n in placeholders {
+		if n >= export.param_list.len() {
+			return Err(format!("{} refers to {{{}}} but ParamList only has {} values", what, n, export.param_list.len()));
+		}
+	}
+	Ok(Described { desc: export.desc.clone(), params: export.param_list.clone() })
+}
+
+// "1-Hit DMG" -> "1_hit_dmg"
+fn slug(label: &str) -> String {
+	let mut slug = String::new();
+	for c in label.chars().flat_map(|c| c.to_lowercase()) {
+		if c.is_alphanumeric() {
+			slug.push(c);
+		} else if !slug.is_empty() && !slug.ends_with('_') {
+			slug.push('_');
+		}
+	}
+	slug.trim_end_matches('_').to_string()
+}
+
+// Indices of the percentage params in a desc line, "{param3:F1P}" is param 3
+fn percent_params(format: &str) -> Vec<usize> {
+	format.split('{')
+		.skip(1)
+		.filter_map(|x| x.split('}').next())
+		.filter(|x| x.ends_with('P'))
+		.filter_map(|x| x.strip_prefix("param")?.split(':').next()?.parse().ok())
+		.collect()
+}
+
+// Every percentage multiplier of a talent, over all 15 levels
+fn hits(skill: &SkillExport, talent: Talent) -> Result<Vec<Hit>, String> {
+	let mut levels: Vec<&PromoteExport> = skill.promote.values().collect();
+	levels.sort_by_key(|x| x.level);
+	if levels.len() < 15 || levels.iter().take(15).enumerate().any(|(i, x)| x.level != i + 1) {
+		return Err(format!("'{}' should have talent levels 1-15, found {}", skill.name, levels.len()));
+	}
+
+	let mut hits = Vec::new();
+	for line in &levels[0].desc {
+		let Some((label, format)) = line.split_once('|') else { continue };
+		let params = percent_params(format);
+		for (n, &param) in params.iter().enumerate() {
+			let id = match n {
+				0 => slug(label),
+				_ => format!("{}_{}", slug(label), n + 1)
+			};
+			// Params count from 1
+			let index = param.checked_sub(1)
+				.ok_or(format!("'{}' refers to param{} for '{}', params start at param1", skill.name, param, label))?;
+			let mut multipliers = [0.0; 15];
+			for (i, level) in levels.iter().take(15).enumerate() {
+				multipliers[i] = level.param.get(index).copied()
+					.ok_or(format!("'{}' level {} is missing param{} for '{}'", skill.name, i + 1, param, label))?
+					* 100.0;
+			}
+			hits.push(Hit { id: leak(id), talent, multipliers });
+		}
+	}
+	Ok(hits)
+}
+
+pub fn parse_character(id: &str, path: &Path) -> Result<&'static Character, String> {
+	let export: CharacterExport = read(path, "character")?;
+	let context = |e: String| format!("{}: {}", path.display(), e);
+	let curves = &export.stats_modifier;
+
+	let mut levels = [[0.0; 3]; 90];
+	for (i, level) in levels.iter_mut().enumerate() {
+		*level = [
+			at_level(&curves.hp, i + 1, "StatsModifier.HP").map_err(context)?,
+			at_level(&curves.atk, i + 1, "StatsModifier.ATK").map_err(context)?,
+			at_level(&curves.def, i + 1, "StatsModifier.DEF").map_err(context)?,
+		];
+	}
+	let mut ascension = [[0.0; 3]; 7];
+	for (i, bonus) in ascension.iter_mut().enumerate() {
+		let props = phase(&curves.ascension, i).map_err(context)?;
+		*bonus = [
+			prop(props, "FIGHT_PROP_BASE_HP"),
+			prop(props, "FIGHT_PROP_BASE_ATTACK"),
+			prop(props, "FIGHT_PROP_BASE_DEFENSE"),
+		];
+	}
+	let ascension_stat = phase(&curves.ascension, 6).map_err(context)?
+		.iter()
+		.find_map(|(key, value)| ascension_stat(key, *value))
+		.ok_or(context("StatsModifier.Ascension has no ascension stat".to_string()))?;
+
+	let talents = [Talent::Normal, Talent::Skill, Talent::Burst];
+	if export.skills.len() < talents.len() {
+		return Err(context(format!("expected normal attack, skill and burst in Skills, found {}", export.skills.len())));
+	}
+	let mut all_hits = Vec::new();
+	for (skill, talent) in export.skills.iter().zip(talents) {
+		all_hits.extend(hits(skill, talent).map_err(context)?);
+	}
+
+	Ok(Box::leak(Box::new(Character {
+		id: leak(id.to_string()),
+		name: leak(export.name),
+		element: element(&export.element).map_err(context)?,
+		weapon: weapon_class(&export.weapon).map_err(context)?,
+		rarity: match export.rarity.as_str() {
+			"QUALITY_ORANGE" => 5,
+			"QUALITY_PURPLE" => 4,
+			x => return Err(context(format!("unknown rarity '{}'", x)))
+		},
+		growth: Growth::Table(Box::leak(Box::new(StatTable {
+			base: [export.base_hp, export.base_atk, export.base_def],
+			levels,
+			ascension,
+		}))),
+		ascension_stat,
+		// Not part of the exports, so imported characters don't get an ER requirement
+		burst_cost: 0.0,
+		skill_particles: 0.0,
+		hits: Box::leak(all_hits.into_boxed_slice()),
+		// Constellations are code, like weapon passives
+		constellations: &[],
+	})))
+}
+
+// Weapons are registered at level 90
+pub fn parse_weapon(id: &str, path: &Path) -> Result<ImportedWeapon, String> {
+	let export: WeaponExport = read(path, "weapon")?;
+	let context = |e: String| format!("{}: {}", path.display(), e);
+	let curves = &export.stats_modifier;
+
+	let base_atk = export.base_atk * at_level(&curves.atk, 90, "StatsModifier.ATK").map_err(context)?
+		+ prop(phase(&curves.ascension, 6).map_err(context)?, "FIGHT_PROP_BASE_ATTACK");
+	let substat = match (&export.sub_stat, &curves.substat) {
+		(Some(substat), Some(curve)) => {
+			let value = substat.base * at_level(curve, 90, "StatsModifier.SubStat").map_err(context)?;
+			weapon_stat(&substat.prop, value).map_err(context)?
+		}
+		(None, None) => return Err(context("weapons without a substat aren't supported".to_string())),
+		_ => return Err(context("SubStat and StatsModifier.SubStat should be given together".to_string()))
+	};
+
+	let mut refinements = Vec::new();
+	if !export.refinement.is_empty() {
+		for refinement in 1..=5 {
+			let entry = export.refinement.get(&refinement.to_string())
+				.ok_or(context(format!("Refinement is missing R{}", refinement)))?;
+			refinements.push(described(entry, &format!("Refinement R{}", refinement)).map_err(context)?);
+		}
+	}
+
+	// Reuse the passive of the built-in weapon, otherwise it's stats only
+	let known = weapons::by_id(id);
+	Ok(ImportedWeapon {
+		weapon: Box::leak(Box::new(Weapon {
+			id: leak(id.to_string()),
+			name: leak(export.name),
+			class: weapon_class(&export.weapon_type).map_err(context)?,
+			rarity: export.rarity,
+			base_atk,
+			substat,
+			passive: match known {
+				Some(weapon) => weapon.passive,
+				None => |_, _| Ok((|_, stats| stats).boxed())
+			},
+		})),
+		passive_modelled: known.is_some(),
+		refinements,
+	})
+}
+
+// Sets with a single bonus only have the 2pc one
+pub fn parse_artifact(id: &str, path: &Path) -> Result<ImportedSet, String> {
+	let export: ArtifactExport = read(path, "artifact set")?;
+	let context = |e: String| format!("{}: {}", path.display(), e);
+	if export.affix.is_empty() || export.affix.len() > 2 {
+		return Err(context(format!("expected the 2pc and 4pc bonus in Affix, found {} entries", export.affix.len())));
+	}
+
+	let mut bonuses = Vec::new();
+	for (affix, pieces) in export.affix.iter().zip([2, 4]) {
+		bonuses.push((pieces, described(affix, &format!("the {}pc bonus", pieces)).map_err(context)?));
+	}
+	Ok(ImportedSet {
+		name: export.name,
+		bonuses_modelled: sets::by_id(id).is_some(),
+		bonuses,
+	})
+}
+
+/////////////////////////////
+///////// Registry //////////
+/////////////////////////////
+
+pub fn path(kind: &str, id: &str) -> PathBuf {
+	Path::new(DATA_DIR).join(kind).join(format!("{}.json", id))
+}
+
+// Ids of the files in data/<kind>, sorted
+pub fn ids(kind: &str) -> Vec<String> {
+	let mut ids: Vec<String> = fs::read_dir(Path::new(DATA_DIR).join(kind))
+		.map(|x| x.flatten()
+			.map(|x| x.path())
+			.filter(|x| x.extension().is_some_and(|x| x == "json"))
+			.filter_map(|x| Some(x.file_stem()?.to_str()?.to_string()))
+			.collect())
+		.unwrap_or_default();
+	ids.sort();
+	ids
+}
+
+// Built-in characters win, then data/characters/<id>.json
+pub fn character(id: &str) -> Result<&'static Character, String> {
+	if let Some(character) = characters::by_id(id) {
+		return Ok(character);
+	}
+	let path = path("characters", id);
+	match path.is_file() {
+		true => parse_character(id, &path),
+		false => Err(format!("unknown character '{}', not built in and no {}", id, path.display()))
+	}
+}
+
+// Built-in weapons win, then data/weapons/<id>.json
+pub fn weapon(id: &str) -> Result<&'static Weapon, String> {
+	if let Some(weapon) = weapons::by_id(id) {
+		return Ok(weapon);
+	}
+	let path = path("weapons", id);
+	match path.is_file() {
+		true => parse_weapon(id, &path).map(|x| x.weapon),
+		false => Err(format!("unknown weapon '{}', not built in and no {}", id, path.display()))
+	}
+}
+
+// Like `Weapon::at`, but a weapon that only came from data/ says so in its
+// name, the rankings shouldn't pass its stats off as the whole weapon
+pub fn weapon_at(weapon: &'static Weapon, refinement: usize) -> Result<WeaponEntry, String> {
+	let mut entry = weapon.at(refinement, &Conditions::default())?;
+	if weapons::by_id(weapon.id).is_none() {
+		entry.name = format!("{} ({})", entry.name, UNMODELLED);
+	}
+	Ok(entry)
+}
+
+#[cfg(test)]
+mod tests {
+	use serde_json::{json, Value};
+	use super::*;
+	use crate::characters::Level;
+
+	fn curve(at_90: f32) -> Value {
+		(1..=90).map(|x| (x.to_string(), json!(1.0 + (at_90 - 1.0) * (x - 1) as f32 / 89.0))).collect()
+	}
+
+	fn skill(name: &str, desc: &[&str], at_1: &[f32]) -> Value {
+		let promote: serde_json::Map<String, Value> = (1..=15).map(|level| (
+			(level - 1).to_string(),
+			json!({ "Level": level, "Desc": desc, "Param": at_1.iter().map(|x| x * level as f32).collect::<Vec<_>>() })
+		)).collect();
+		json!({ "Name": name, "Promote": promote })
+	}
+
+	fn export() -> Value {
+		let mut ascension = vec![json!({}); 7];
+		ascension[6] = json!({ "FIGHT_PROP_BASE_HP": 1000.0, "FIGHT_PROP_CRITICAL": 0.192 });
+		json!({
+			"Name": "Test",
+			"Rarity": "QUALITY_ORANGE",
+			"Weapon": "WEAPON_CATALYST",
+			"Element": "Water",
+			"BaseHP": 1000.0,
+			"BaseATK": 20.0,
+			"BaseDEF": 50.0,
+			"StatsModifier": { "HP": curve(10.0), "ATK": curve(10.0), "DEF": curve(10.0), "Ascension": ascension },
+			"Skills": [
+				skill("Normal Attack", &["1-Hit DMG|{param1:F1P}", "Charged Attack DMG|{param2:F1P}+{param3:F1P}"], &[0.5, 1.0, 1.5]),
+				skill("Skill", &["Bite DMG|{param1:F1P} Max HP", "Duration|{param2:F1}s"], &[0.1, 6.0]),
+				skill("Burst", &["Skill DMG|{param1:F1P}", ""], &[1.0]),
+			]
+		})
+	}
+
+	fn write(name: &str, value: &Value) -> PathBuf {
+		let path = std::env::temp_dir().join(format!("genshin-rs-{}-{}.json", std::process::id(), name));
+		fs::write(&path, value.to_string()).unwrap();
+		path
+	}
+
+	#[test]
+	fn imports_character_exports() {
+		let character = parse_character("test", &write("character", &export())).unwrap();
+		let stats = character.base(Level::MAX).unwrap();
+		assert_eq!(stats.hp, 11000.0);
+		assert!((stats.crit_rate - 24.2).abs() < 1e-4);
+
+		let ids: Vec<_> = character.hits.iter().map(|x| x.id).collect();
+		assert_eq!(ids, ["1_hit_dmg", "charged_attack_dmg", "charged_attack_dmg_2", "bite_dmg", "skill_dmg"]);
+		assert_eq!(character.hit("bite_dmg", &Default::default()).unwrap().talent, Talent::Skill);
+		assert!((character.hit("charged_attack_dmg_2", &Default::default()).unwrap().multipliers[9] - 1500.0).abs() < 1e-3);
+	}
+
+	#[test]
+	fn imports_other_ascension_stats() {
+		for (prop, physical) in [("FIGHT_PROP_PHYSICAL_ADD_HURT", 28.8), ("FIGHT_PROP_HEAL_ADD", 0.0)] {
+			let mut export = export();
+			export["StatsModifier"]["Ascension"][6] = json!({ "FIGHT_PROP_BASE_HP": 1000.0, prop: 0.288 });
+			let character = parse_character("test", &write(prop, &export)).unwrap();
+			let stats = character.base(Level::MAX).unwrap();
+			assert!((stats.elemental_bonus[Element::Physical] - physical).abs() < 1e-4, "{}", prop);
+			assert_eq!(stats.elemental_bonus[Element::Hydro], 0.0);
+		}
+	}
+
+	#[test]
+	fn rejects_param0() {
+		let mut export = export();
+		export["Skills"][2] = skill("Burst", &["Skill DMG|{param0:F1P}"], &[1.0]);
+		let error = parse_character("test", &write("param0", &export)).err().unwrap();
+		assert!(error.contains("param0"), "{}", error);
+	}
+
+	fn weapon_export() -> Value {
+		let mut ascension = vec![json!({}); 7];
+		ascension[6] = json!({ "FIGHT_PROP_BASE_ATTACK": 100.0 });
+		json!({
+			"Name": "Test Spear",
+			"Rarity": 4,
+			"WeaponType": "WEAPON_POLE",
+			"BaseATK": 44.0,
+			"SubStat": { "Prop": "FIGHT_PROP_CRITICAL", "Base": 0.06 },
+			"StatsModifier": { "ATK": curve(10.0), "SubStat": curve(4.6), "Ascension": ascension },
+			"Refinement": (1..=5).map(|x| (x.to_string(), json!({ "Desc": "ATK +{0}%", "ParamList": [0.15 + 0.05 * x as f32] })))
+				.collect::<serde_json::Map<String, Value>>()
+		})
+	}
+
+	#[test]
+	fn imports_weapon_exports() {
+		let path = write("weapon", &weapon_export());
+		let imported = parse_weapon("test_spear", &path).unwrap();
+		assert_eq!(imported.weapon.class, WeaponClass::Polearm);
+		assert_eq!(imported.weapon.base_atk, 540.0);
+		assert_eq!(imported.weapon.substat.0, WeaponStat::CritRate);
+		assert!((imported.weapon.substat.1 - 27.6).abs() < 1e-3);
+		assert!(!imported.passive_modelled);
+		assert!(weapon_at(imported.weapon, 1).unwrap().name.ends_with(&format!("({})", UNMODELLED)));
+		assert_eq!(imported.refinements.len(), 5);
+		assert_eq!(imported.refinements[0].desc, "ATK +{0}%");
+		assert!((imported.refinements[4].params[0] - 0.4).abs() < 1e-6);
+
+		// A built-in id keeps its passive
+		assert!(parse_weapon("homa", &path).unwrap().passive_modelled);
+
+		let mut broken = weapon_export();
+		broken["SubStat"]["Prop"] = json!("FIGHT_PROP_SHIELD");
+		let error = parse_weapon("test_spear", &write("broken_weapon", &broken)).err().unwrap();
+		assert!(error.contains("FIGHT_PROP_SHIELD"), "{}", error);
+
+		let mut broken = weapon_export();
+		broken["Refinement"].as_object_mut().unwrap().remove("3");
+		let error = parse_weapon("test_spear", &write("missing_refinement", &broken)).err().unwrap();
+		assert!(error.contains("Refinement is missing R3"), "{}", error);
+		broken["Refinement"]["3"] = json!({ "Desc": "ATK +{0}%, EM +{1}", "ParamList": [0.25] });
+		let error = parse_weapon("test_spear", &write("missing_param", &broken)).err().unwrap();
+		assert!(error.contains("Refinement R3 refers to {1} but ParamList only has 1 values"), "{}", error);
+
+		// Weapons without a passive don't have a Refinement block
+		let mut plain = weapon_export();
+		plain.as_object_mut().unwrap().remove("Refinement");
+		assert!(parse_weapon("test_spear", &write("plain_weapon", &plain)).unwrap().refinements.is_empty());
+	}
+
+	#[test]
+	fn imports_artifact_exports() {
+		let export = json!({
+			"Name": "Test Set",
+			"Affix": [
+				{ "Desc": "Elemental Mastery +{0}.", "ParamList": [80.0] },
+				{ "Desc": "ATK +{0}% per teammate of the same element, EM +{1} per other one.", "ParamList": [0.14, 50.0] }
+			]
+		});
+		let set = parse_artifact("test_set", &write("artifact", &export)).unwrap();
+		assert_eq!(set.name, "Test Set");
+		assert!(!set.bonuses_modelled);
+		assert_eq!(set.bonuses.iter().map(|x| x.0).collect::<Vec<_>>(), [2, 4]);
+		assert_eq!(set.bonuses[1].1.params, [0.14, 50.0]);
+		// Catalog sets have their bonuses in code
+		assert!(parse_artifact("gilded", &write("artifact", &export)).unwrap().bonuses_modelled);
+
+		let mut broken = export.clone();
+		broken["Affix"][1]["ParamList"] = json!([0.14]);
+		let error = parse_artifact("test_set", &write("broken_artifact", &broken)).err().unwrap();
+		assert!(error.contains("the 4pc bonus refers to {1}"), "{}", error);
+		broken["Affix"] = json!([]);
+		assert!(parse_artifact("test_set", &write("broken_artifact", &broken)).is_err());
+	}
+
+	#[test]
+	fn reports_schema_drift() {
+		let mut drifted = export();
+		drifted.as_object_mut().unwrap().remove("BaseHP");
+		let error = parse_character("test", &write("drifted", &drifted)).err().unwrap();
+		assert!(error.contains("doesn't look like a character export"), "{}", error);
+		assert!(error.contains("BaseHP"), "{}", error);
+	}
+}
@@ -0,0 +1,655 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use serde::Deserialize;
+
+use crate::*;
+use crate::artifacts::ArtifactBuild;
+use crate::buffs::{BoxedBuff, Buff, Source};
+use crate::calculators::Rotation;
+use crate::characters::{self, Character, Config, Scaling, Talent};
+use crate::damage::{damage, Reaction};
+use crate::enemies::Enemy;
+use crate::rolls::RollPolicy;
+use crate::energy::Energy;
+use crate::gauge::{self, Event};
+use crate::timeline::{self, Step, TimedHit, Timeline, Window};
+use crate::weapons::{Conditions, WeaponEntry};
+
+// A rotation described in a TOML or JSON file, so that adding a team
+// doesn't need a recompile. See the `rotations/` directory for examples.
+#[derive(Deserialize)]
+pub struct RotationFile {
+	pub name: String,
+	pub character: String,
+	pub weapon: Option<BuffSpec>,
+	pub artifact_set: Option<BuffSpec>,
+	#[serde(default)]
+	pub buffs: Vec<BuffSpec>,
+	pub actions: Vec<Action>,
+	// How long the rotation lasts in s, for timed actions it defaults to
+	// when the last one ends
+	pub length: Option<f32>,
+	// What the rest of the team applies, vaporize and melt are then worked
+	// out hit by hit instead of taken from `reaction`
+	#[serde(default)]
+	pub applications: Vec<Application>,
+	// Particles and flat energy over a rotation, builds then need the ER
+	// to burst every rotation
+	pub energy: Option<Energy>,
+}
+
+// A teammate applying their element at `at`, then `every` s `until` a given time,
+// i.e. `{ element = "hydro", at = 1, every = 2, until = 18 }`
+#[derive(Deserialize)]
+pub struct Application {
+	pub element: Element,
+	#[serde(default = "one_unit")]
+	pub units: f32,
+	pub at: f32,
+	pub every: Option<f32>,
+	pub until: Option<f32>,
+}
+
+// A buff or weapon by name, along with whatever parameters it takes, i.e.
+// `{ name = "furina_burst", fanfare = 150 }`
+#[derive(Deserialize)]
+pub struct BuffSpec {
+	pub name: String,
+	#[serde(flatten)]
+	pub params: HashMap<String, Param>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+pub enum Param {
+	Bool(bool),
+	Number(f32),
+	Element(Element),
+	Elements(Vec<Element>),
+}
+
+#[derive(Clone, Deserialize)]
+pub struct Action {
+	pub talent: Talent,
+	// Talent multiplier in %, of the scaling stat
+	pub multiplier: Option<f32>,
+	// Or one of the character's hits, i.e. `skill_tap`, at the configured talent level
+	pub hit: Option<String>,
+	pub scaling: Scaling,
+	// Flat damage added on top of the hit in %, of the scaling stat
+	#[serde(default)]
+	pub additive_multiplier: f32,
+	// Defaults to the element of the character
+	pub element: Option<Element>,
+	#[serde(default)]
+	pub reaction: Reaction,
+	#[serde(default = "one")]
+	pub count: usize,
+	// When the action starts in s, either every action has one or none do.
+	// The `count` hits are spread over `duration`.
+	pub at: Option<f32>,
+	#[serde(default)]
+	pub duration: f32,
+	#[serde(default)]
+	pub cooldown: f32,
+	// Gauge units the hit applies and its ICD tag, "none" for hits without
+	// one. Hits of the same talent share a tag by default.
+	#[serde(default = "one_unit")]
+	pub units: f32,
+	pub icd: Option<String>,
+}
+
+fn one() -> usize {
+	1
+}
+
+fn one_unit() -> f32 {
+	1.0
+}
+
+impl Action {
+	fn multiplier(&self, character: &Character, config: &Config) -> Result<f32, String> {
+		match (self.multiplier, &self.hit) {
+			(Some(x), None) => Ok(x),
+			(None, Some(id)) => character.hit(id, config)
+				.map(|_| character.multiplier(id, config))
+				.ok_or(format!("{} has no hit '{}' at C{}", character.name, id, config.constellation)),
+			_ => Err("every action needs either a multiplier or a hit".to_string())
+		}
+	}
+
+	pub fn damage(&self, multiplier: f32, stats: &CharStats, enemy: &Enemy) -> f32 {
+		let scaling = match self.scaling {
+			Scaling::Hp => stats.hp,
+			Scaling::Atk => stats.atk,
+			Scaling::Def => stats.def,
+			Scaling::Em => stats.em,
+		};
+		let (talent_bonus, flat_bonus) = match self.talent {
+			Talent::Normal => (stats.na_bonus, stats.na_bonus_flat),
+			Talent::Charged => (stats.ca_bonus, 0.0),
+			Talent::Skill => (stats.skill_bonus, 0.0),
+			Talent::Burst => (0.0, 0.0),
+		};
+
+		damage(
+			stats,
+			self.element.unwrap_or(stats.element),
+			scaling * multiplier / 100.0 + flat_bonus,
+			scaling * self.additive_multiplier / 100.0,
+			talent_bonus,
+			self.reaction,
+			enemy
+		) * self.count as f32
+	}
+}
+
+pub fn load(path: &Path) -> Result<RotationFile, String> {
+	let text = fs::read_to_string(path)
+		.map_err(|e| format!("{}: {}", path.display(), e))?;
+	parse(&text, path.extension().and_then(|x| x.to_str()))
+		.map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+// `format` is the extension the text would have as a file
+pub fn parse(text: &str, format: Option<&str>) -> Result<RotationFile, String> {
+	match format {
+		Some("toml") => toml::from_str(text).map_err(|e| e.to_string()),
+		Some("json") => serde_json::from_str(text).map_err(|e| e.to_string()),
+		_ => Err("expected a .toml or .json file".to_string())
+	}
+}
+
+impl RotationFile {
+	// Turns the file into the closure shape `setup_calculator` consumes
+	pub fn rotation(
+		&self,
+		config: &Config
+	) -> Result<impl Rotation, String> {
+		let character = importer::character(&self.character)
+			.map_err(|e| format!("{}: {}", self.name, e))?;
+		let base_stats = character.base(config.level)?;
+
+		let timed = self.timed()?;
+		let mut team_buffs = Vec::new();
+		let mut windows = Vec::new();
+		// A set picked by the config replaces the file's own. It takes the
+		// conditions given with it, and the uptime and window.
+		let mut listed: Vec<(&BuffSpec, BoxedBuff)> = Vec::new();
+		match (config.set, &self.artifact_set) {
+			(Some(choice), Some(spec)) => {
+				let conditions = conditions(spec, &TIMING).map_err(|e| format!("{}: {}", self.name, e))?;
+				for set_buff in choice.buffs(&conditions).map_err(|e| format!("{}: {}", self.name, e))? {
+					listed.push((spec, with_uptime(spec, set_buff).map_err(|e| format!("{}: {}", self.name, e))?));
+				}
+			}
+			(Some(choice), None) => team_buffs.extend(choice.buffs(&Conditions::default())?),
+			(None, Some(spec)) => listed.push((spec, buff(spec).map_err(|e| format!("{}: {}", self.name, e))?)),
+			(None, None) => {}
+		}
+		for spec in &self.buffs {
+			listed.push((spec, buff(spec).map_err(|e| format!("{}: {}", self.name, e))?));
+		}
+		for (spec, buff) in listed {
+			match window(spec).map_err(|e| format!("{}: {}", self.name, e))? {
+				Some(_) if !timed => return Err(format!("{}: '{}' has a window, which needs actions with `at` times", self.name, spec.name)),
+				Some((start, duration)) => windows.push(Window { buff, start, duration }),
+				None => team_buffs.push(buff)
+			}
+		}
+		team_buffs.extend(character.buffs(config));
+		buffs::validate(&team_buffs).map_err(|e| format!("{}: {}", self.name, e))?;
+
+		// Timed actions are split into their hits
+		let mut resolved = Vec::new();
+		for action in &self.actions {
+			let multiplier = action.multiplier(character, config)
+				.map_err(|e| format!("{}: {}", self.name, e))?;
+			match action.at {
+				Some(at) => for i in 0..action.count {
+					let time = at + action.duration * i as f32 / action.count as f32;
+					resolved.push((time, multiplier, Action { count: 1, ..action.clone() }));
+				},
+				None => resolved.push((0.0, multiplier, action.clone()))
+			}
+		}
+
+		if !self.applications.is_empty() {
+			if !timed {
+				return Err(format!("{}: `applications` need actions with `at` times", self.name));
+			}
+			let events = self.events(&resolved, character.element).map_err(|e| format!("{}: {}", self.name, e))?;
+			let reactions = gauge::simulate(&events);
+			let own = reactions.len() - resolved.len();
+			for ((_, _, action), reaction) in resolved.iter_mut().zip(&reactions[own..]) {
+				if matches!(action.reaction, Reaction::None | Reaction::ForwardVaporize | Reaction::ReverseVaporize | Reaction::ForwardMelt | Reaction::ReverseMelt) {
+					action.reaction = *reaction;
+				}
+			}
+		}
+
+		let hits = resolved.into_iter()
+			.map(|(time, multiplier, action)| TimedHit {
+				time,
+				damage: Box::new(move |stats: &CharStats, enemy: &Enemy| action.damage(multiplier, stats, enemy))
+			})
+			.collect();
+		let timeline = Timeline::new(&self.steps(), team_buffs, windows, hits)
+			.map_err(|e| format!("{}: {}", self.name, e))?;
+
+		Ok(move |build: &ArtifactBuild, substats: &Substats, policy: &RollPolicy, base: &dyn Fn(CharStats) -> CharStats, weapon_buff: &BoxedBuff, enemy: &Enemy| {
+			timeline.run(base_stats, base, weapon_buff, build, substats, policy, enemy)
+		})
+	}
+
+	// Teammates' applications first, so they win ties, then every hit
+	fn events(&self, hits: &[(f32, f32, Action)], element: Element) -> Result<Vec<Event>, String> {
+		let mut events = Vec::new();
+		for application in &self.applications {
+			let Application { element: applied, units, at, every, until } = *application;
+			let name = format!("the {:?} application at {}s", applied, at).to_lowercase();
+			match (every, until) {
+				(None, None) => events.push(Event { time: at, element: applied, units, icd: None }),
+				(Some(every), Some(until)) if every > 0.0 => events.extend(gauge::repeated(applied, units, at, every, until)),
+				(Some(every), Some(_)) => return Err(format!("{} repeats every {}s, it should be above 0", name, every)),
+				(Some(_), None) => return Err(format!("{} has `every` but no `until`", name)),
+				(None, Some(_)) => return Err(format!("{} has `until` but no `every`", name)),
+			}
+		}
+		for (time, _, action) in hits {
+			let tag = action.icd.clone().unwrap_or(format!("{:?}", action.talent).to_lowercase());
+			events.push(Event {
+				time: *time,
+				element: action.element.unwrap_or(element),
+				units: action.units,
+				icd: (tag != "none").then_some(tag),
+			});
+		}
+		Ok(events)
+	}
+
+	// Whether actions have start times
+	fn timed(&self) -> Result<bool, String> {
+		match self.actions.iter().filter(|x| x.at.is_some()).count() {
+			0 => Ok(false),
+			n if n == self.actions.len() => Ok(true),
+			_ => Err(format!("{}: either every action has an `at` time or none do", self.name))
+		}
+	}
+
+	fn steps(&self) -> Vec<Step> {
+		self.actions.iter()
+			.filter_map(|x| Some(Step {
+				name: x.hit.clone().unwrap_or(format!("{:?}", x.talent).to_lowercase()),
+				start: x.at?,
+				duration: x.duration,
+				cooldown: x.cooldown,
+			}))
+			.collect()
+	}
+
+	// Lowest ER in % that gets the burst back every rotation
+	pub fn min_er(&self) -> Result<Option<f32>, String> {
+		match &self.energy {
+			Some(energy) => energy.min_er(importer::character(&self.character)?)
+				.map_err(|e| format!("{}: {}", self.name, e)),
+			None => Ok(None)
+		}
+	}
+
+	// How long the rotation lasts in s, if it's timed or given
+	pub fn length(&self) -> Result<Option<f32>, String> {
+		let end = match self.timed()? {
+			true => timeline::check_steps(&self.steps()).map_err(|e| format!("{}: {}", self.name, e))?,
+			false => 0.0
+		};
+		match self.length {
+			Some(x) if x < end => Err(format!("{}: `length` is {}s but the last action ends at {}s", self.name, x, end)),
+			Some(x) if x > 0.0 => Ok(Some(x)),
+			Some(x) => Err(format!("{}: `length` {} should be above 0", self.name, x)),
+			None if end > 0.0 => Ok(Some(end)),
+			None => Ok(None)
+		}
+	}
+
+	// The weapon the file was written for, if any
+	pub fn weapon(&self) -> Result<Option<WeaponEntry>, String> {
+		match &self.weapon {
+			Some(spec) => weapon(spec).map(Some).map_err(|e| format!("{}: {}", self.name, e)),
+			None => Ok(None)
+		}
+	}
+}
+
+struct Params<'a>(&'a BuffSpec);
+
+impl Params<'_> {
+	fn get(&self, key: &str) -> Result<&Param, String> {
+		self.0.params.get(key)
+			.ok_or(format!("'{}' is missing parameter '{}'", self.0.name, key))
+	}
+
+	fn num(&self, key: &str) -> Result<f32, String> {
+		match self.get(key)? {
+			Param::Number(x) => Ok(*x),
+			_ => Err(format!("'{}': parameter '{}' should be a number", self.0.name, key))
+		}
+	}
+
+	fn int(&self, key: &str) -> Result<usize, String> {
+		match self.num(key)? {
+			x if x >= 0.0 && x.fract() == 0.0 => Ok(x as usize),
+			x => Err(format!("'{}': parameter '{}' should be a whole number, got {}", self.0.name, key, x))
+		}
+	}
+
+	fn stacks(&self, key: &str, max: usize) -> Result<usize, String> {
+		match self.int(key)? {
+			x if x <= max => Ok(x),
+			x => Err(format!("'{}': parameter '{}' is {}, past the most of {}", self.0.name, key, x, max))
+		}
+	}
+
+	fn refinement(&self) -> Result<usize, String> {
+		match self.int("refinement")? {
+			x @ 1..=5 => Ok(x),
+			x => Err(format!("'{}': refinement {} is not within 1-5", self.0.name, x))
+		}
+	}
+
+	// A supporting character at 90, with the DEF% and flat DEF their build
+	// adds on top of their base stats, i.e. `def_percent = 175, flat_def = 300`
+	fn supporter(&self, character: &Character) -> Result<CharStats, String> {
+		let mut stats = character.base(characters::Level::MAX)?;
+		let optional = |key| match self.0.params.contains_key(key) {
+			true => self.num(key),
+			false => Ok(0.0)
+		};
+		stats.def += stats.def * optional("def_percent")? / 100.0 + optional("flat_def")?;
+		Ok(stats)
+	}
+
+	fn flag(&self, key: &str) -> Result<bool, String> {
+		match self.get(key)? {
+			Param::Bool(x) => Ok(*x),
+			_ => Err(format!("'{}': parameter '{}' should be true or false", self.0.name, key))
+		}
+	}
+
+	fn element(&self, key: &str) -> Result<Element, String> {
+		match self.get(key)? {
+			Param::Element(x) => Ok(*x),
+			_ => Err(format!("'{}': parameter '{}' should be an element", self.0.name, key))
+		}
+	}
+
+	fn elements(&self, key: &str) -> Result<Vec<Element>, String> {
+		match self.get(key)? {
+			Param::Elements(x) => Ok(x.clone()),
+			Param::Element(x) => Ok(vec![*x]),
+			_ => Err(format!("'{}': parameter '{}' should be a list of elements", self.0.name, key))
+		}
+	}
+}
+
+// Any buff can be given an `uptime` on top of its own parameters
+fn buff(spec: &BuffSpec) -> Result<BoxedBuff, String> {
+	let p = Params(spec);
+	let buff: BoxedBuff = match spec.name.as_str() {
+		// Generic stat sticks for anything we don't have a function for yet
+		"atk_percent" => { let x = p.num("value")?; buffs::named("ATK%", Source::Character, move |base, mut stats| { stats.atk += base.atk * x / 100.0; stats }).stacking().boxed() },
+		"hp_percent" => { let x = p.num("value")?; buffs::named("HP%", Source::Character, move |base, mut stats| { stats.hp += base.hp * x / 100.0; stats }).stacking().boxed() },
+		"def_percent" => { let x = p.num("value")?; buffs::named("DEF%", Source::Character, move |base, mut stats| { stats.def += base.def * x / 100.0; stats }).stacking().boxed() },
+		"em" => { let x = p.num("value")?; buffs::named("EM", Source::Character, move |_, mut stats| { stats.em += x; stats }).stacking().boxed() },
+		"dmg_bonus" => { let x = p.num("value")?; buffs::named("DMG%", Source::Character, move |_, mut stats| { stats.dmg_bonus += x; stats }).stacking().boxed() },
+		"crit_rate" => { let x = p.num("value")?; buffs::named("CR", Source::Character, move |_, mut stats| { stats.crit_rate += x; stats }).stacking().boxed() },
+		"crit_damage" => { let x = p.num("value")?; buffs::named("CD", Source::Character, move |_, mut stats| { stats.crit_damage += x; stats }).stacking().boxed() },
+
+		// Characters
+		"nahida_burst" => buffs::nahida_burst(p.num("em")?).boxed(),
+		"xilonen_shred" => buffs::xilonen_shred(&p.elements("party_elements")?).boxed(),
+		"vv_shred" => buffs::vv_shred(p.element("swirled")?).boxed(),
+		"kazuha_e" => buffs::kazuha_e(p.element("swirled")?).boxed(),
+		"thoma_c6" => buffs::thoma_c6().boxed(),
+		"zhong_shred" => buffs::zhong_shred().boxed(),
+		"tao_skill" => buffs::tao_skill().boxed(),
+		"tao_a4" => buffs::tao_a4().boxed(),
+		"bennett_burst" => buffs::bennett_burst().boxed(),
+		"furina_burst" => buffs::furina_burst(p.num("fanfare")?).boxed(),
+		"yelan_a4" => buffs::yelan_a4().boxed(),
+		"albedo_burst" => buffs::albedo_burst().boxed(),
+		"yun_burst" => buffs::yun_burst(&p.supporter(&characters::YUN)?).boxed(),
+		"pyro_resonance" => buffs::pyro_resonance(&p.elements("party")?).boxed(),
+		"hydro_resonance" => buffs::hydro_resonance(&p.elements("party")?).boxed(),
+
+		// Artifacts
+		"hod2pc" => buffs::hod2pc().boxed(),
+		"hod" => buffs::hod().boxed(),
+		"reverie2pc" => buffs::reverie2pc().boxed(),
+		"reverie" => buffs::reverie(p.stacks("stacks", 5)?).boxed(),
+		"troupe2pc" => buffs::troupe2pc().boxed(),
+		"troupe" => buffs::troupe(p.flag("is_off_field")?).boxed(),
+		"gilded2pc" => buffs::gilded2pc().boxed(),
+		"gilded" => match (p.stacks("same_type", 3)?, p.stacks("other_type", 3)?) {
+			(same, other) if same + other <= 3 => buffs::gilded(same, other).boxed(),
+			(same, other) => return Err(format!("'{}': {} party members is more than the other three", spec.name, same + other))
+		},
+		"bollide" => buffs::bollide().boxed(),
+		"instructor2" => buffs::instructor2().boxed(),
+		"instructor_share" => buffs::instructor_share().boxed(),
+		"tenacity2pc" => buffs::tenacity2pc().boxed(),
+		"tenacity" => buffs::tenacity().boxed(),
+		"mh2pc" => buffs::mh2pc().boxed(),
+		"mh" => buffs::mh().boxed(),
+		"shime2pc" => buffs::shime2pc().boxed(),
+		"shime" => buffs::shime().boxed(),
+		"obsidian2pc" => buffs::obsidian2pc().boxed(),
+		"obsidian" => buffs::obsidian().boxed(),
+		"scroll" => buffs::scroll(&p.elements("reaction_elements")?, p.flag("saurian")?).boxed(),
+		"petra_share" => buffs::petra_share(p.element("crystallized")?).boxed(),
+
+		name => return Err(format!("unknown buff '{}'", name))
+	};
+
+	with_uptime(spec, buff)
+}
+
+// Parameters every buff can take besides its own, see `with_uptime` and `window`
+const TIMING: [&str; 3] = ["uptime", "start", "duration"];
+
+fn with_uptime(spec: &BuffSpec, buff: BoxedBuff) -> Result<BoxedBuff, String> {
+	if !spec.params.contains_key("uptime") {
+		return Ok(buff);
+	}
+	match Params(spec).num("uptime")? {
+		x @ 0.0..=1.0 => Ok(buff.with_uptime(x).boxed()),
+		x => Err(format!("'{}': uptime {} is not within 0-1", spec.name, x))
+	}
+}
+
+// `start` and `duration` in s, to only apply a buff to the hits inside
+fn window(spec: &BuffSpec) -> Result<Option<(f32, f32)>, String> {
+	let p = Params(spec);
+	match (spec.params.contains_key("start"), spec.params.contains_key("duration")) {
+		(false, false) => Ok(None),
+		(true, true) => Ok(Some((p.num("start")?, p.num("duration")?))),
+		_ => Err(format!("'{}': a window needs both `start` and `duration`", spec.name))
+	}
+}
+
+// Weapons come out of the registry, every other parameter is a passive condition
+fn weapon(spec: &BuffSpec) -> Result<WeaponEntry, String> {
+	let weapon = importer::weapon(&spec.name)?;
+	let refinement = match spec.params.contains_key("refinement") {
+		true => Params(spec).refinement()?,
+		false => 1
+	};
+
+	weapon.at(refinement, &conditions(spec, &["refinement"])?)
+}
+
+// Parameters as passive or set conditions, except for the `skipped` ones
+fn conditions(spec: &BuffSpec, skipped: &[&str]) -> Result<Conditions, String> {
+	let mut conditions = Conditions::default();
+	for (key, param) in spec.params.iter().filter(|x| !skipped.contains(&x.0.as_str())) {
+		match param {
+			Param::Number(x) => conditions.set(key, *x),
+			Param::Bool(x) => conditions.set(key, if *x { 1.0 } else { 0.0 }),
+			_ => return Err(format!("'{}': parameter '{}' should be a number or true/false", spec.name, key))
+		}
+	}
+	Ok(conditions)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::artifacts::ArtifactStat;
+	use crate::sets::{self, SetChoice};
+	use crate::rotations;
+	use crate::weapons::{self, Conditions};
+
+	// What the built-ins of the same name do, written as files
+	const SHARK: &str = r#"
+		name = "shark_furina_thoma_kazuha"
+		character = "shark"
+		artifact_set = { name = "obsidian" }
+		length = 20
+		buffs = [
+			{ name = "thoma_c6" },
+			{ name = "hydro_resonance", party = ["hydro", "hydro", "pyro", "anemo"] },
+			{ name = "scroll", reaction_elements = ["pyro", "hydro"], saurian = false },
+			{ name = "furina_burst", fanfare = 150, start = 1, duration = 18 },
+			{ name = "kazuha_e", swirled = "hydro", start = 4, duration = 8 },
+			{ name = "vv_shred", swirled = "hydro", start = 4, duration = 10 },
+		]
+		applications = [
+			{ element = "pyro", at = 3, every = 1, until = 18 },
+		]
+
+		[[actions]]
+		talent = "normal"
+		multiplier = 15.6
+		scaling = "hp"
+		additive_multiplier = 7.8
+		reaction = "forward_vaporize"
+		icd = "none"
+		at = 5.5
+		duration = 1.5
+
+		[[actions]]
+		talent = "normal"
+		multiplier = 15.6
+		scaling = "hp"
+		additive_multiplier = 62.5
+		reaction = "forward_vaporize"
+		icd = "none"
+		count = 3
+		at = 7
+		duration = 4.5
+
+		[[actions]]
+		talent = "burst"
+		multiplier = 105.2
+		scaling = "hp"
+		reaction = "forward_vaporize"
+		at = 11.5
+		duration = 1.5
+	"#;
+
+	const FRAUD: &str = r#"{
+		"name": "fraud_yelan_furina_xilonen",
+		"character": "fraud",
+		"artifact_set": { "name": "obsidian" },
+		"length": 20,
+		"buffs": [
+			{ "name": "xilonen_shred", "party_elements": ["pyro", "hydro"] },
+			{ "name": "hydro_resonance", "party": ["pyro", "hydro", "hydro", "geo"] },
+			{ "name": "atk_percent", "value": 35 },
+			{ "name": "yelan_a4", "start": 1, "duration": 15 },
+			{ "name": "furina_burst", "fanfare": 100, "start": 2, "duration": 18 }
+		],
+		"applications": [
+			{ "element": "hydro", "at": 1, "every": 2.5, "until": 18 }
+		],
+		"actions": [
+			{ "talent": "skill", "hit": "skill_cast", "scaling": "atk", "reaction": "forward_vaporize", "at": 4.5, "duration": 0.5 },
+			{ "talent": "skill", "hit": "skill_tap", "scaling": "atk", "reaction": "forward_vaporize", "count": 7, "at": 5, "duration": 7 }
+		]
+	}"#;
+
+	fn same_dpr(file: &RotationFile, weapon: &str) {
+		let config = Config::default();
+		let loaded = file.rotation(&config).unwrap();
+		let builtin = rotations::by_name(&file.name, config).unwrap().unwrap();
+		assert_eq!(file.length().unwrap(), Some(builtin.length));
+		let builtin = builtin.rotation;
+		let weapon = weapons::by_id(weapon).unwrap().at(1, &Conditions::default()).unwrap();
+		let base = |stats| weapon.weapon.base(stats);
+		let enemy = enemies::MASANORI;
+		let build = ArtifactBuild::layout(ArtifactStat::Em, ArtifactStat::ElementalDmg, ArtifactStat::CritDamage).unwrap();
+		for substats in [[0; 7], [2, 4, 6, 0, 8, 0, 2], [10, 0, 0, 10, 10, 0, 0]] {
+			let policy = RollPolicy::default();
+			let from_file = loaded(&build, &substats, &policy, &base, &weapon.buff, &enemy).0;
+			let from_rust = builtin(&build, &substats, &policy, &base, &weapon.buff, &enemy).0;
+			assert!((from_file / from_rust - 1.0).abs() < 1e-4, "{}: {} vs {}", file.name, from_file, from_rust);
+		}
+	}
+
+	#[test]
+	fn files_reproduce_the_builtins() {
+		same_dpr(&parse(SHARK, Some("toml")).unwrap(), "surfing_time");
+		same_dpr(&parse(FRAUD, Some("json")).unwrap(), "sun");
+	}
+
+	#[test]
+	fn rejects_open_ended_applications() {
+		let mut file = parse(SHARK, Some("toml")).unwrap();
+		file.applications[0].until = None;
+		let error = file.rotation(&Config::default()).err().unwrap();
+		assert!(error.contains("the pyro application at 3s has `every` but no `until`"), "{}", error);
+	}
+
+	#[test]
+	fn picked_sets_keep_the_window() {
+		let mut file = parse(SHARK, Some("toml")).unwrap();
+		file.artifact_set = Some(toml::from_str("name = \"obsidian\"\nstacks = 2\nstart = 0\nduration = 6").unwrap());
+		let config = Config { set: Some(SetChoice::Four(sets::by_id("reverie").unwrap())), ..Config::default() };
+		let rotation = file.rotation(&config).unwrap();
+
+		let build = ArtifactBuild::layout(ArtifactStat::Em, ArtifactStat::ElementalDmg, ArtifactStat::CritDamage).unwrap();
+		let weapon = weapons::by_id("surfing_time").unwrap().at(1, &Conditions::default()).unwrap();
+		let (_, _, active) = rotation(&build, &[0; 7], &RollPolicy::default(), &|x| weapon.weapon.base(x), &weapon.buff, &enemies::MASANORI);
+		let reverie = active.iter().find(|x| x.name == "Crimson Reverie 4pc").unwrap();
+		// Only the hits in the first 6s are inside of the window
+		assert!(reverie.uptime > 0.0 && reverie.uptime < 1.0, "{}", reverie.uptime);
+	}
+
+	#[test]
+	fn rejects_bad_parameters() {
+		let spec = |text: &str| toml::from_str::<BuffSpec>(text).unwrap();
+		assert!(buff(&spec("name = \"reverie\"\nstacks = 3")).is_ok());
+		let error = buff(&spec("name = \"reverie\"\nstacks = 1.5")).err().unwrap();
+		assert!(error.contains("whole number"), "{}", error);
+		assert!(buff(&spec("name = \"reverie\"\nstacks = -1")).is_err());
+		assert!(buff(&spec("name = \"furina_burst\"\nfanfare = 100\nuptime = 1.5")).is_err());
+		assert!(window(&spec("name = \"bennett_burst\"\nstart = 1")).is_err());
+	}
+
+	#[test]
+	fn rejects_conditions_the_passive_cant_be_at() {
+		let mut file = parse(SHARK, Some("toml")).unwrap();
+		file.weapon = Some(toml::from_str("name = \"serpent_spine\"\nstacks = 5").unwrap());
+		assert!(file.weapon().is_ok());
+		file.weapon = Some(toml::from_str("name = \"serpent_spine\"\nstacks = 7").unwrap());
+		let error = file.weapon().err().unwrap();
+		assert!(error.contains("'stacks' should be a whole number within 0-5, got 7"), "{}", error);
+		file.weapon = Some(toml::from_str("name = \"serpent_spine\"\nstacks = 2.5").unwrap());
+		assert!(file.weapon().is_err());
+
+		file.artifact_set = Some(toml::from_str("name = \"obsidian\"\nsame_type = 2\nother_type = 2").unwrap());
+		let config = Config { set: Some(SetChoice::Four(sets::by_id("gilded").unwrap())), ..Config::default() };
+		assert!(file.rotation(&config).is_err());
+	}
+}
@@ -1,18 +1,119 @@
-use buffs::MagicBoxed;
+use std::fmt;
+use std::ops::{Index, IndexMut};
+use serde::{Deserialize, Serialize};
 
+use buffs::{ActiveBuff, Buff};
+use artifacts::{ArtifactBuild, ArtifactStat};
+use rolls::{RollModel, RollPolicy, RollTier};
+
+mod artifacts;
 mod buffs;
+mod cli;
 mod characters;
 mod rotations;
 mod calculators;
+mod damage;
+mod energy;
+mod enemies;
+mod gauge;
+mod importer;
+mod loader;
+mod optimizer;
+mod reports;
+mod rolls;
+mod sets;
+mod timeline;
+mod weapons;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Element {
+	Pyro,
+	Hydro,
+	Electro,
+	Cryo,
+	Anemo,
+	Geo,
+	Dendro,
+	Physical
+}
+
+impl Element {
+	pub const ALL: [Element; 8] = [
+		Element::Pyro,
+		Element::Hydro,
+		Element::Electro,
+		Element::Cryo,
+		Element::Anemo,
+		Element::Geo,
+		Element::Dendro,
+		Element::Physical
+	];
+}
+
+// A fixed-size map keyed by element, so that CharStats stays Copy
+#[derive(Copy, Clone, Debug, Default, Serialize)]
+pub struct ElementMap(pub [f32; 8]);
+
+impl ElementMap {
+	pub const ZERO: ElementMap = ElementMap([0.0; 8]);
+
+	// Applies to every hit, physical included
+	pub fn add_all(&mut self, value: f32) {
+		for v in self.0.iter_mut() {
+			*v += value;
+		}
+	}
+
+	// "Elemental DMG Bonus" never applies to physical hits
+	pub fn add_elemental(&mut self, value: f32) {
+		for element in Element::ALL {
+			if element != Element::Physical {
+				self[element] += value;
+			}
+		}
+	}
+
+	pub fn blend(self, other: ElementMap, t: f32) -> ElementMap {
+		let mut out = self;
+		for (v, o) in out.0.iter_mut().zip(other.0) {
+			*v += (o - *v) * t;
+		}
+		out
+	}
+}
+
+impl Index<Element> for ElementMap {
+	type Output = f32;
+	fn index(&self, element: Element) -> &f32 {
+		&self.0[element as usize]
+	}
+}
+
+impl IndexMut<Element> for ElementMap {
+	fn index_mut(&mut self, element: Element) -> &mut f32 {
+		&mut self.0[element as usize]
+	}
+}
+
+// [EM, HP%, ATK%, BONUS%, CR, CD, DEF%, ER%] summed over all artifact mainstats,
+// see `artifacts::ArtifactBuild::mainstats`
+pub type Mainstats = [f32; 8];
+
+// Number of substat rolls into [HP%, ATK%, EM, CR, CD, DEF%, ER%]
+pub type Substats = [usize; 7];
 
 // We'll be generating these algorithmically to find
 // the best possible outcome
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, Serialize)]
 pub struct CharStats {
 	hp: f32,
 	atk: f32,
+	def: f32,
 	em: f32,
+	energy_recharge: f32,
 	dmg_bonus: f32,
+	elemental_bonus: ElementMap,
 	na_bonus: f32,
 	na_bonus_flat: f32,
 	skill_bonus: f32,
@@ -20,115 +121,138 @@ pub struct CharStats {
 	reaction_bonus: f32,
 	crit_rate: f32,
 	crit_damage: f32,
-	res_shred: f32
+	res_shred: ElementMap,
+	element: Element,
+	level: usize
 }
 
-impl ToString for CharStats {
-	fn to_string(&self) -> String {
-		format!(
-			"HP: {},\nATK: {},\nEM: {},\nDMG%: {},\nCR: {},\nCD: {}\n--------------",
+impl CharStats {
+	// Total DMG% that applies to a hit of the given element
+	pub fn dmg_bonus_for(&self, element: Element) -> f32 {
+		self.dmg_bonus + self.elemental_bonus[element]
+	}
+
+	// Weighs the difference a buff made by how long it's up for
+	pub fn blend(self, buffed: CharStats, uptime: f32) -> CharStats {
+		let mix = |a: f32, b: f32| a + (b - a) * uptime;
+		CharStats {
+			hp: mix(self.hp, buffed.hp),
+			atk: mix(self.atk, buffed.atk),
+			def: mix(self.def, buffed.def),
+			em: mix(self.em, buffed.em),
+			energy_recharge: mix(self.energy_recharge, buffed.energy_recharge),
+			dmg_bonus: mix(self.dmg_bonus, buffed.dmg_bonus),
+			elemental_bonus: self.elemental_bonus.blend(buffed.elemental_bonus, uptime),
+			na_bonus: mix(self.na_bonus, buffed.na_bonus),
+			na_bonus_flat: mix(self.na_bonus_flat, buffed.na_bonus_flat),
+			skill_bonus: mix(self.skill_bonus, buffed.skill_bonus),
+			ca_bonus: mix(self.ca_bonus, buffed.ca_bonus),
+			reaction_bonus: mix(self.reaction_bonus, buffed.reaction_bonus),
+			crit_rate: mix(self.crit_rate, buffed.crit_rate),
+			crit_damage: mix(self.crit_damage, buffed.crit_damage),
+			res_shred: self.res_shred.blend(buffed.res_shred, uptime),
+			element: self.element,
+			level: self.level,
+		}
+	}
+}
+
+impl fmt::Display for CharStats {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(
+			f,
+			"HP: {},\nATK: {},\nDEF: {},\nEM: {},\nER: {},\nDMG%: {},\nCR: {},\nCD: {}\n--------------",
 			self.hp,
 			self.atk,
+			self.def,
 			self.em,
-			self.dmg_bonus,
+			self.energy_recharge,
+			self.dmg_bonus_for(self.element),
 			self.crit_rate,
 			self.crit_damage
 		)
 	}
 }
 
+// The KQM standard, see `rolls::RollPolicy`
 fn stats_kqms(
 	base: CharStats,
 	weapon: impl Fn(CharStats) -> CharStats,
-	dynamic_buffs: Vec<&dyn Fn(CharStats, CharStats) -> CharStats>,
-	mainstat_em: f32,
-	mainstat_hp: f32,
-	mainstat_atk: f32,
-	mainstat_elemental: f32,
-	mainstat_cr: f32,
-	mainstat_cd: f32,
-	mut hp_rolls: isize,
-	mut atk_rolls: isize,
-	mut em_rolls: isize,
-	mut crit_rate_rolls: isize,
-	mut crit_damage_rolls: isize,
+	dynamic_buffs: Vec<Box<dyn Buff + '_>>,
+	build: &ArtifactBuild,
+	rolls: &Substats,
 ) -> CharStats {
-	// max 10 per stat!
-	hp_rolls = hp_rolls.min(10);
-	atk_rolls = atk_rolls.min(10);
-	hp_rolls = hp_rolls.min(10);
-	em_rolls = em_rolls.min(10);
-	crit_rate_rolls = crit_rate_rolls.min(10);
-	crit_damage_rolls = crit_damage_rolls.min(10);
-
-	// we need to decrease the amount of rolls in substats
-	// based on the mainstats of artifacts
-	hp_rolls -= ((mainstat_hp / 46.6) * 2.0).round() as isize;
-	atk_rolls -= ((mainstat_atk / 46.6) * 2.0).round() as isize;
-	em_rolls -= ((mainstat_em / 187.0) * 2.0).round() as isize;
-	crit_rate_rolls -= ((mainstat_cr / 31.1) * 2.0).round() as isize;
-	crit_damage_rolls -= ((mainstat_cd / 62.2) * 2.0).round() as isize;
+	let share = RollTier::average();
+	let mut values = [0.0; 7];
+	for ((value, stat), rolls) in values.iter_mut().zip(rolls::SUBSTAT_ORDER).zip(rolls) {
+		let liquid = (*rolls).min(rolls::kqms_cap(stat, build));
+		*value = (rolls::KQMS_FIXED + liquid) as f32 * rolls::max_roll(stat) * share;
+	}
+	let flat = [ArtifactStat::Hp, ArtifactStat::Atk, ArtifactStat::Def]
+		.map(|x| rolls::KQMS_FIXED as f32 * rolls::max_roll(x) * share);
+	stats_from(base, weapon, dynamic_buffs, &build.mainstats(), &values, flat)
+}
 
-	let base = weapon(base);
-	let mut dynamic = CharStats {
-		hp: 4780.0 + base.hp + base.hp * (hp_rolls as f32 * 4.96 + mainstat_hp) / 100.0,
-		atk: 311.0 + base.atk + base.atk * ((atk_rolls as f32 * 4.96) / 100.0 + mainstat_atk / 100.0),
-		crit_rate: base.crit_rate + mainstat_cr + crit_rate_rolls as f32 * 3.31,
-		crit_damage: base.crit_damage + mainstat_cd + crit_damage_rolls as f32 * 6.62,
-		dmg_bonus: base.dmg_bonus + mainstat_elemental,
-		reaction_bonus: 0.0,
-		na_bonus: 0.0,
-		skill_bonus: 0.0,
-		ca_bonus: 0.0,
-		em: base.em + mainstat_em + em_rolls as f32 * 19.82,
-		res_shred: 0.0,
-		na_bonus_flat: 0.0,
-	};
-	dynamic.hp += base.hp * (2.0 * 4.96) / 100.0;
-	dynamic.hp += 2.0 * 253.0;
-	dynamic.atk += base.atk * (2.0 * 4.96) / 100.0;
-	dynamic.atk += 2.0 * 16.54;
-	dynamic.em += 2.0 * 16.82;
-	dynamic.crit_rate += 2.0 * 3.31;
-	dynamic.crit_damage += 2.0 * 6.62;
+// Regular buffs first, in order, then every conversion off of the same
+// pre-conversion stats so that they can't silently feed into each other
+fn apply_buffs(
+	base: CharStats,
+	mut stats: CharStats,
+	dynamic_buffs: &[Box<dyn Buff + '_>]
+) -> CharStats {
 	for buff in dynamic_buffs {
-		dynamic = buff(base, dynamic);
+		stats = buff.apply(base, stats);
 	}
-	dynamic
+	let pre = stats;
+	for buff in dynamic_buffs {
+		stats = buff.convert(base, pre, stats);
+	}
+	stats
 }
 
 // Assume we always roll into % and never flat. Ignore minrolls.
+#[allow(clippy::too_many_arguments)]
 fn stats_raw(
 	base: CharStats,
 	weapon: impl Fn(CharStats) -> CharStats,
-	dynamic_buffs: Vec<&dyn Fn(CharStats, CharStats) -> CharStats>,
+	dynamic_buffs: Vec<Box<dyn Buff + '_>>,
 	mainstat_em: f32,
 	mainstat_hp: f32,
 	mainstat_atk: f32,
 	mainstat_elemental: f32,
 	mainstat_cr: f32,
 	mainstat_cd: f32,
+	mainstat_def: f32,
+	mainstat_er: f32,
 	hp_rolls: isize,
 	atk_rolls: isize,
 	em_rolls: isize,
 	crit_rate_rolls: isize,
 	crit_damage_rolls: isize,
+	def_rolls: isize,
+	er_rolls: isize,
 ) -> CharStats {
 	let base = weapon(base);
 	let mut dynamic = CharStats {
 		hp: 4780.0 + base.hp + base.hp * (hp_rolls as f32 * 4.96 + mainstat_hp) / 100.0,
 		atk: 311.0 + base.atk + base.atk * ((atk_rolls as f32 * 4.96) / 100.0 + mainstat_atk / 100.0),
+		def: base.def + base.def * (def_rolls as f32 * 6.2 + mainstat_def) / 100.0,
+		energy_recharge: base.energy_recharge + mainstat_er + er_rolls as f32 * 5.51,
 		crit_rate: base.crit_rate + mainstat_cr + crit_rate_rolls as f32 * 3.31,
 		crit_damage: base.crit_damage + mainstat_cd + crit_damage_rolls as f32 * 6.62,
-		dmg_bonus: base.dmg_bonus + mainstat_elemental,
+		dmg_bonus: base.dmg_bonus,
+		elemental_bonus: base.elemental_bonus,
 		reaction_bonus: 0.0,
 		na_bonus: 0.0,
 		skill_bonus: 0.0,
 		ca_bonus: 0.0,
 		em: base.em + mainstat_em + em_rolls as f32 * 19.82,
-		res_shred: 0.0,
+		res_shred: ElementMap::ZERO,
 		na_bonus_flat: 0.0,
+		element: base.element,
+		level: base.level,
 	};
+	dynamic.elemental_bonus[base.element] += mainstat_elemental;
 	dynamic.hp += base.hp * (2.0 * 4.96) / 100.0;
 	dynamic.hp += 2.0 * 253.0;
 	dynamic.atk += base.atk * (2.0 * 4.96) / 100.0;
@@ -136,192 +260,107 @@ fn stats_raw(
 	dynamic.em += 2.0 * 16.82;
 	dynamic.crit_rate += 2.0 * 3.31;
 	dynamic.crit_damage += 2.0 * 6.62;
-	for buff in dynamic_buffs {
-		dynamic = buff(base, dynamic);
-	}
-	dynamic
+	dynamic.def += base.def * (2.0 * 6.2) / 100.0;
+	dynamic.def += 2.0 * 19.68;
+	dynamic.energy_recharge += 2.0 * 5.51;
+	apply_buffs(base, dynamic, &dynamic_buffs)
 }
 
-// A wrapper over stats_raw that decomposes parameters from vectors
-fn stats(
+// Rolls on actual pieces, see `rolls`. Anything past what the pieces can
+// roll into a stat is lost.
+fn stats_rolls(
 	base: CharStats,
 	weapon: impl Fn(CharStats) -> CharStats,
-	dynamic_buffs: Vec<&dyn Fn(CharStats, CharStats) -> CharStats>,
-	mainstats: &[f32; 6],
-	rolls: &[usize; 5],
+	dynamic_buffs: Vec<Box<dyn Buff + '_>>,
+	build: &ArtifactBuild,
+	rolls: &Substats,
+	model: &RollModel,
 ) -> CharStats {
-	stats_kqms(
-		base,
-		weapon,
-		dynamic_buffs,
-		mainstats[0],
-		mainstats[1],
-		mainstats[2],
-		mainstats[3],
-		mainstats[4],
-		mainstats[5],
-		rolls[0] as isize,
-		rolls[1] as isize,
-		rolls[2] as isize,
-		rolls[3] as isize,
-		rolls[4] as isize
-	)
-}
-
-fn main() {
-	// We want to investigate various mainstat variations
-	let arti_mainstat_distributions = vec![
-		// EM      HP    ATK   BONUS%  CR    CD
-		[  561.0,  0.0,  0.0,  0.0,    0.0,  0.0   ],  // Triple EM
-		[  374.0,  0.0,  0.0,  0.0,    0.0,  62.2  ],  // Double EM + CD
-		[  374.0,  0.0,  0.0,  0.0,    31.1, 0.0   ],  // Double EM + CR
-		[  187.0,  0.0,  0.0,  46.6,   0.0,  62.2  ],  // EM + Dmg + CD
-		[  187.0,  46.6, 0.0,  0.0,    31.1, 0.0   ],  // EM + HP + CR
-		[  187.0,  46.6, 0.0,  0.0,    0.0,  62.2  ],  // EM + HP + CD
-		[  0.0,    46.6, 0.0,  46.6,   0.0,  62.2  ],  // HP + Dmg + CD
-		[  0.0,    46.6, 0.0,  46.6,   31.1, 0.0   ],  // HP + Dmg + CR
-		[  187.0,  0.0,  46.6, 0.0,    31.1, 0.0   ],  // EM + ATK + CR
-		[  187.0,  0.0,  46.6, 0.0,    0.0,  62.2  ],  // EM + ATK + CD
-		[  0.0,    0.0,  46.6, 46.6,   0.0,  62.2  ],  // ATK + Dmg + CD
-		[  0.0,    0.0,  46.6, 46.6,   31.1, 0.0   ],  // ATK + Dmg + CR
-	];
-
-	// Assuming we have n max-rolls to distribute across substats,
-	// find all possible substat combinations (ignore minrolls)
-	let num_maxrolls = 20;
-	let mut arti_substat_distributions = Vec::new();
-	for hp in 0..(1+num_maxrolls) {
-		for atk in 0..(1+num_maxrolls-hp) {
-			for em in 0..(1+num_maxrolls-atk-hp) {
-				for cr in 0..(1+num_maxrolls-atk-hp-em) {
-					for cd in 0..(1+num_maxrolls-atk-hp-em-cr) {
-						arti_substat_distributions.push([hp, atk, em, cr, cd]);
-					}
-				}
-			}
-		}
-	}
-
-	let catalysts: Vec<(&str, &dyn Fn(CharStats) -> CharStats, Box<dyn Fn(CharStats, CharStats) -> CharStats>)> = vec![
-		("Surfing Time R1", &buffs::surfing_time_base, buffs::surfing_time_buff(1, 4).boxed()),
-		("Surfing Time R5", &buffs::surfing_time_base, buffs::surfing_time_buff(5, 4).boxed()),
-		("Sacrificial Jade R1", &buffs::sac_jade_base, buffs::sac_jade_buff(1).boxed()),
-		("Sacrificial Jade R5", &buffs::sac_jade_base, buffs::sac_jade_buff(5).boxed()),
-		("Ring of Yaxche R1", &buffs::ceiba_base, buffs::ceiba_buff(1).boxed()),
-		("Ring of Yaxche R5", &buffs::ceiba_base, buffs::ceiba_buff(5).boxed()),
-		("Magic Guide R5", &buffs::magic_guide_base, buffs::magic_guide_buff(false).boxed()),
-		("Solar Pearl R1", &buffs::solar_pearl_base, buffs::solar_pearl_buff(1).boxed()),
-		("Solar Pearl R5", &buffs::solar_pearl_base, buffs::solar_pearl_buff(5).boxed()),
-		("The Widsith R1 [ATK]", &buffs::widsith_base, buffs::widsith_buff(1, 0).boxed()),
-		("The Widsith R1 [DMG]", &buffs::widsith_base, buffs::widsith_buff(1, 1).boxed()),
-		("The Widsith R1 [EM]", &buffs::widsith_base, buffs::widsith_buff(1, 2).boxed()),
-		("The Widsith R1 [None]", &buffs::widsith_base, buffs::widsith_buff(1, 3).boxed()),
-		("The Widsith R5 [ATK]", &buffs::widsith_base, buffs::widsith_buff(5, 0).boxed()),
-		("The Widsith R5 [DMG]", &buffs::widsith_base, buffs::widsith_buff(5, 1).boxed()),
-		("The Widsith R5 [EM]", &buffs::widsith_base, buffs::widsith_buff(5, 2).boxed()),
-		("The Widsith R5 [None]", &buffs::widsith_base, buffs::widsith_buff(5, 3).boxed()),
-		("Floating Dreams R1", &buffs::floating_dreams_base, buffs::floating_dreams_buff(1, 0, 3).boxed()),
-		("Floating Dreams R5", &buffs::floating_dreams_base, buffs::floating_dreams_buff(5, 0, 3).boxed()),
-		("Tome of Eternal Flow R1", &buffs::tome_base, buffs::tome_buff(1, 0).boxed()),
-		("Tome of Eternal Flow R5", &buffs::tome_base, buffs::tome_buff(5, 0).boxed()),
-	];
-
-	let spears: Vec<(&str, &dyn Fn(CharStats) -> CharStats, Box<dyn Fn(CharStats, CharStats) -> CharStats>)> = vec![
-		("Lumidouce Elegy R1", &buffs::lumidouce_base, buffs::lumidouce_buff(1, 2).boxed()),
-		("Lumidouce Elegy R5", &buffs::lumidouce_base, buffs::lumidouce_buff(5, 2).boxed()),
-		("Staff of Homa R1", &buffs::homa_base, buffs::homa_buff(false).boxed()),
-		("Scarlet Sands R1", &buffs::soss_base, buffs::soss_buff(1).boxed()),
-		("Dragon's Bane R1", &buffs::dragons_base, buffs::dragons_buff(1).boxed()),
-		("Dragon's Bane R5", &buffs::dragons_base, buffs::dragons_buff(5).boxed()),
-		("Missive Windspear R1", &buffs::missive_base, buffs::missive_buff(1).boxed()),
-		("Missive Windspear R5", &buffs::missive_base, buffs::missive_buff(5).boxed()),
-	];
-
-	let claymores: Vec<(&str, &dyn Fn(CharStats) -> CharStats, Box<dyn Fn(CharStats, CharStats) -> CharStats>)> = vec![
-		("Earth Shaker R1", &buffs::earth_shaker_base, buffs::earth_shaker_buff(1).boxed()),
-		("Earth Shaker R5", &buffs::earth_shaker_base, buffs::earth_shaker_buff(5).boxed()),
-		("Tidal Shadow R1", &buffs::tidal_shadow_base, buffs::tidal_shadow_buff(1, true).boxed()),
-		("Tidal Shadow R5", &buffs::tidal_shadow_base, buffs::tidal_shadow_buff(5, true).boxed()),
-		("Mailed Flower R1", &buffs::mailed_flower_base, buffs::mailed_flower_buff(1, true).boxed()),
-		("Mailed Flower R5", &buffs::mailed_flower_base, buffs::mailed_flower_buff(5, true).boxed()),
-		("Serpent Spine R1", &buffs::serpent_spine_base, buffs::serpent_spine_buff(1, 5).boxed()),
-		("Serpent Spine R5", &buffs::serpent_spine_base, buffs::serpent_spine_buff(5, 5).boxed()),
-		("Rainslasher R1", &buffs::rainslasher_base, buffs::rainslasher_buff(1, true).boxed()),
-		("Rainslasher R5", &buffs::rainslasher_base, buffs::rainslasher_buff(5, true).boxed()),
-		("Sun R1", &buffs::sun_base, buffs::sun_buff(1, true, true).boxed()),
-		("Sun R5", &buffs::sun_base, buffs::sun_buff(5, true, true).boxed()),
-		("Wolf's Gravestone R1", &buffs::wgs_base, buffs::wgs_buff(1).boxed()),
-		("Wolf's Gravestone R5", &buffs::wgs_base, buffs::wgs_buff(5).boxed()),
-	];
-
-	calculators::weapon_calculator(
-		claymores,
-		arti_mainstat_distributions,
-		arti_substat_distributions,
-		"Sun R1",
-		|mainstats, substats, base, buff| {
-			rotations::fraud_yelan_furina_xilonen(mainstats, substats, base, buff)
-		}
-	);
-
-	/*
-
-	// We're gonna keep track of all builds
-	let mut all_damage = Vec::with_capacity(
-		arti_mainstat_distributions.len() *
-		arti_substat_distributions.len()
-	);
-
-	for mainstats in arti_mainstat_distributions {
-		for substats in &arti_substat_distributions {
-			let stats_base = stats(
-				characters::FRAUD,
-				&buffs::sun_base,
-				vec![
-					&buffs::sun_buff(1, true, true)
-				],
-				&mainstats,
-				substats
-			);
-
-			let damage = rotations::fraud_yelan_furina_xilonen(
-				&mainstats,
-				&substats,
-				buffs::sun_base,
-				buffs::sun_buff(1, true, true)
-			);
-
-			let mut distribution = mainstats.to_vec();
-			distribution.extend(substats.map(|x| x as f32));
-			all_damage.push((damage, distribution, stats_base));
-		}
+	let rolls = model.capped(rolls, build);
+	let mut values = [0.0; 7];
+	for ((value, stat), rolls) in values.iter_mut().zip(rolls::SUBSTAT_ORDER).zip(rolls) {
+		*value = rolls as f32 * model.value(stat);
 	}
+	stats_from(base, weapon, dynamic_buffs, &build.mainstats(), &values, model.flat_stats())
+}
 
-	// Decide target distribution
-	all_damage.sort_by_key(|x| x.0 as usize);
-	let median = all_damage.get(all_damage.len() / 2).unwrap();
-	let minimum = all_damage.first().unwrap();
-	let maximum = all_damage.last().unwrap();
+// Mainstats, flat HP and ATK from the flower and plume, and what the
+// substats are worth: [HP%, ATK%, EM, CR, CD, DEF%, ER%] and flat [HP, ATK, DEF]
+fn stats_from(
+	base: CharStats,
+	weapon: impl Fn(CharStats) -> CharStats,
+	dynamic_buffs: Vec<Box<dyn Buff + '_>>,
+	mainstats: &Mainstats,
+	substats: &[f32; 7],
+	[flat_hp, flat_atk, flat_def]: [f32; 3],
+) -> CharStats {
+	let base = weapon(base);
+	let mut dynamic = CharStats {
+		hp: ArtifactStat::Hp.main_value() + flat_hp + base.hp + base.hp * (substats[0] + mainstats[1]) / 100.0,
+		atk: ArtifactStat::Atk.main_value() + flat_atk + base.atk + base.atk * (substats[1] + mainstats[2]) / 100.0,
+		def: flat_def + base.def + base.def * (substats[5] + mainstats[6]) / 100.0,
+		energy_recharge: base.energy_recharge + mainstats[7] + substats[6],
+		crit_rate: base.crit_rate + mainstats[4] + substats[3],
+		crit_damage: base.crit_damage + mainstats[5] + substats[4],
+		dmg_bonus: base.dmg_bonus,
+		elemental_bonus: base.elemental_bonus,
+		reaction_bonus: 0.0,
+		na_bonus: 0.0,
+		skill_bonus: 0.0,
+		ca_bonus: 0.0,
+		em: base.em + mainstats[0] + substats[2],
+		res_shred: ElementMap::ZERO,
+		na_bonus_flat: 0.0,
+		element: base.element,
+		level: base.level,
+	};
+	dynamic.elemental_bonus[base.element] += mainstats[3];
+	apply_buffs(base, dynamic, &dynamic_buffs)
+}
 
-	// Decompose from target
-	let (damage, distribution, stats) = maximum;
+// Hands back which of the buffs went into the result. They're checked
+// with `buffs::validate` when the rotation is built, not here.
+fn stats(
+	base: CharStats,
+	weapon: impl Fn(CharStats) -> CharStats,
+	dynamic_buffs: Vec<Box<dyn Buff + '_>>,
+	build: &ArtifactBuild,
+	rolls: &Substats,
+	policy: &RollPolicy,
+) -> (CharStats, Vec<ActiveBuff>) {
+	let active = dynamic_buffs.iter().map(|x| ActiveBuff::of(x.as_ref())).collect();
+	let mainstats = build.mainstats();
+	let stats = match policy {
+		RollPolicy::Kqms => stats_kqms(base, weapon, dynamic_buffs, build, rolls),
+		RollPolicy::Raw { .. } => stats_raw(
+			base,
+			weapon,
+			dynamic_buffs,
+			mainstats[0],
+			mainstats[1],
+			mainstats[2],
+			mainstats[3],
+			mainstats[4],
+			mainstats[5],
+			mainstats[6],
+			mainstats[7],
+			rolls[0] as isize,
+			rolls[1] as isize,
+			rolls[2] as isize,
+			rolls[3] as isize,
+			rolls[4] as isize,
+			rolls[5] as isize,
+			rolls[6] as isize
+		),
+		RollPolicy::Custom(model) => stats_rolls(base, weapon, dynamic_buffs, build, rolls, model),
+	};
+	(stats, active)
+}
 
-	// This will print the optimal build
-	// It will output the number of substat rolls denoted with 'r' at end.
-	let labels = vec!["EM", "HP", "ATK", "BONUS%", "CR", "CD", "HP%r", "ATK%r", "EMr", "CRr", "CDr"];
-	println!("OPTIMAL ARTIFACT MAINSTATS:");
-	for i in 0..6 {
-		println!("{}\t{}", labels[i], distribution[i]);
-	}
-	println!("\nOPTIMAL ARTIFACT SUBSTAT ROLLS:");
-	for i in 6..11 {
-		println!("{}\t{}", labels[i], distribution[i]);
+fn main() {
+	let args: Vec<String> = std::env::args().skip(1).collect();
+	if let Err(e) = cli::run(&args) {
+		eprintln!("{}", e);
+		std::process::exit(1);
 	}
-
-	println!("\nBASE STATS WITH ARTI:");
-	println!("{}", stats.to_string());
-
-	// Print a summary of the stats (incl. buffs)
-	println!("DPR: {}", damage);
-	*/
-}
\ No newline at end of file
+}
@@ -0,0 +1,201 @@
+use crate::Substats;
+
+// Finding the best substat distribution by trying every single one of them
+// grows combinatorially with the number of rolls and stats. Damage never
+// goes down when we add a roll to any stat (caps just flatten it out), so we
+// can get away with much less.
+
+// Hands out rolls one by one to whichever stat gains the most from it,
+// then keeps moving single rolls between stats while that improves damage.
+pub fn greedy(
+	num_rolls: usize,
+	eval: impl Fn(&Substats) -> f32
+) -> (Substats, f32) {
+	let mut best = [0; 7];
+	for _ in 0..num_rolls {
+		let mut best_gain = (0, f32::MIN);
+		for i in 0..best.len() {
+			best[i] += 1;
+			let damage = eval(&best);
+			best[i] -= 1;
+			if damage > best_gain.1 {
+				best_gain = (i, damage);
+			}
+		}
+		best[best_gain.0] += 1;
+	}
+
+	let mut best_damage = eval(&best);
+	loop {
+		let mut improved = false;
+		for from in 0..best.len() {
+			for to in 0..best.len() {
+				if from == to || best[from] == 0 {
+					continue;
+				}
+				let mut candidate = best;
+				candidate[from] -= 1;
+				candidate[to] += 1;
+				let damage = eval(&candidate);
+				if damage > best_damage {
+					best = candidate;
+					best_damage = damage;
+					improved = true;
+				}
+			}
+		}
+		if !improved {
+			break;
+		}
+	}
+	(best, best_damage)
+}
+
+// Exact search. Stats are fixed one at a time, and giving all of the
+// remaining rolls to every unfixed stat at once bounds whatever the subtree
+// can reach. `bound` has to be monotonic in every stat and at least `eval`
+// of any distribution it covers; that's `eval` itself when damage is
+// monotonic already. Seeded with the greedy result so most of the tree gets
+// pruned right away. Subtrees that can't reach `floor` aren't searched
+// either, so when nothing does the greedy result is all we get. One that
+// only ties it still is, and the result is the same as without a floor.
+pub fn branch_and_bound(
+	num_rolls: usize,
+	eval: impl Fn(&Substats) -> f32,
+	bound: impl Fn(&Substats) -> f32,
+	floor: f32
+) -> (Substats, f32) {
+	let mut best = greedy(num_rolls, &eval);
+	let mut current = [0; 7];
+	branch(&mut current, 0, num_rolls, &eval, &bound, floor, &mut best);
+	best
+}
+
+fn branch(
+	current: &mut Substats,
+	index: usize,
+	remaining: usize,
+	eval: &impl Fn(&Substats) -> f32,
+	bound: &impl Fn(&Substats) -> f32,
+	floor: f32,
+	best: &mut (Substats, f32)
+) {
+	// The last stat gets whatever's left, damage is monotonic after all
+	if index == current.len() - 1 {
+		current[index] = remaining;
+		let damage = eval(current);
+		if damage > best.1 {
+			*best = (*current, damage);
+		}
+		current[index] = 0;
+		return;
+	}
+
+	for rolls in (0..=remaining).rev() {
+		current[index] = rolls;
+		let mut most = *current;
+		for x in most.iter_mut().skip(index + 1) {
+			*x = remaining - rolls;
+		}
+		let most = bound(&most);
+		if most > best.1 && most >= floor {
+			branch(current, index + 1, remaining - rolls, eval, bound, floor, best);
+		}
+	}
+	current[index] = 0;
+}
+
+// Every distribution of exactly `num_rolls` rolls, what `main` used to do
+#[cfg(test)]
+pub fn brute_force(
+	num_rolls: usize,
+	eval: impl Fn(&Substats) -> f32
+) -> (Substats, f32) {
+	let mut best = ([0; 7], f32::MIN);
+	let mut current = [0; 7];
+	enumerate(&mut current, 0, num_rolls, &mut |substats| {
+		let damage = eval(substats);
+		if damage > best.1 {
+			best = (*substats, damage);
+		}
+	});
+	best
+}
+
+#[cfg(test)]
+fn enumerate(
+	current: &mut Substats,
+	index: usize,
+	remaining: usize,
+	visit: &mut impl FnMut(&Substats)
+) {
+	if index == current.len() - 1 {
+		current[index] = remaining;
+		visit(current);
+		return;
+	}
+	for rolls in 0..=remaining {
+		current[index] = rolls;
+		enumerate(current, index + 1, remaining - rolls, visit);
+	}
+	current[index] = 0;
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{enemies, rotations, weapons};
+	use crate::artifacts::{ArtifactBuild, ArtifactStat};
+
+	// Crit-like product with a cap on one of the stats, roughly
+	// the shape of the real thing
+	fn synthetic(substats: &Substats) -> f32 {
+		let atk = 1.0 + 0.05 * substats[1] as f32;
+		let em = 1.0 + 0.02 * substats[2] as f32;
+		let crit_rate = (0.05 + 0.033 * substats[3] as f32).min(1.0);
+		let crit_damage = 0.5 + 0.066 * substats[4] as f32;
+		atk * em * (1.0 + crit_rate * crit_damage)
+	}
+
+	// Ties can land on different distributions, so only the damage is
+	// compared, and only up to float noise
+	fn close(a: f32, b: f32) -> bool {
+		(a - b).abs() <= 1e-5 * b.abs().max(1.0)
+	}
+
+	#[test]
+	fn matches_brute_force_on_synthetic() {
+		for num_rolls in [0, 1, 5, 12] {
+			let expected = brute_force(num_rolls, synthetic).1;
+			assert!(close(branch_and_bound(num_rolls, synthetic, synthetic, f32::MIN).1, expected));
+			// Greedy is a heuristic, it can only ever fall short
+			assert!(greedy(num_rolls, synthetic).1 <= expected * (1.0 + 1e-5));
+		}
+	}
+
+	#[test]
+	fn matches_brute_force_on_rotation() {
+		let build = ArtifactBuild::layout(ArtifactStat::Em, ArtifactStat::ElementalDmg, ArtifactStat::CritRate).unwrap();
+		let sun = weapons::by_id("sun").unwrap().at(1, &Default::default()).unwrap();
+		let rotation = rotations::by_name("fraud_yelan_furina_xilonen", Default::default()).unwrap().unwrap().rotation;
+		let eval = |substats: &Substats| rotation(
+			&build,
+			substats,
+			&Default::default(),
+			&|stats| sun.weapon.base(stats),
+			&sun.buff,
+			&enemies::MASANORI
+		).0;
+		let expected = brute_force(10, eval).1;
+		assert!(close(branch_and_bound(10, eval, eval, f32::MIN).1, expected));
+		assert!(greedy(10, eval).1 <= expected * (1.0 + 1e-5));
+	}
+
+	// Another job finding the same damage first can't change what this one finds
+	#[test]
+	fn floors_only_prune_what_falls_short() {
+		let unbounded = branch_and_bound(12, synthetic, synthetic, f32::MIN);
+		assert_eq!(branch_and_bound(12, synthetic, synthetic, unbounded.1), unbounded);
+		assert_eq!(branch_and_bound(12, synthetic, synthetic, unbounded.1 * 2.0), greedy(12, synthetic));
+	}
+}
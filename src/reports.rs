@@ -0,0 +1,366 @@
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::Substats;
+use crate::artifacts::{Artifact, Slot};
+use crate::calculators::{RankedWeapon, WeaponRanking};
+
+pub const SUBSTAT_LABELS: [&str; 7] = ["HP%", "ATK%", "EM", "CR", "CD", "DEF%", "ER%"];
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Format {
+	Table,
+	Csv,
+	Json,
+	Markdown,
+}
+
+impl FromStr for Format {
+	type Err = String;
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s {
+			"table" => Ok(Format::Table),
+			"csv" => Ok(Format::Csv),
+			"json" => Ok(Format::Json),
+			"markdown" | "md" => Ok(Format::Markdown),
+			_ => Err(format!("unknown format '{}', expected table, csv, json or markdown", s))
+		}
+	}
+}
+
+pub fn render(ranking: &WeaponRanking, format: Format) -> Result<String, String> {
+	match format {
+		Format::Table => Ok(table(ranking)),
+		Format::Csv => Ok(csv(ranking)),
+		Format::Json => json(ranking),
+		Format::Markdown => Ok(markdown(ranking)),
+	}
+}
+
+pub fn export(ranking: &WeaponRanking, format: Format, output: Option<&Path>) -> Result<(), String> {
+	write_output(&render(ranking, format)?, output)
+}
+
+// Prints to stdout unless we're given a file to write into
+pub fn write_output(text: &str, output: Option<&Path>) -> Result<(), String> {
+	match output {
+		Some(path) => fs::write(path, text).map_err(|e| format!("{}: {}", path.display(), e)),
+		None => {
+			print!("{}", text);
+			Ok(())
+		}
+	}
+}
+
+fn of_reference(x: Option<f32>) -> String {
+	match x {
+		Some(x) => (x as usize).to_string(),
+		None => "-".to_string()
+	}
+}
+
+// Mainstat first, then the substats it rolled, i.e. "CD, 5x CR / 2x ATK"
+pub fn describe_piece(piece: &Artifact) -> String {
+	let parts: Vec<String> = piece.substats.iter()
+		.map(|(stat, rolls)| format!("{}x {}", rolls, stat))
+		.collect();
+	match parts.is_empty() {
+		true => piece.main_stat.to_string(),
+		false => format!("{}, {}", piece.main_stat, parts.join(" / "))
+	}
+}
+
+pub fn describe_substats(substats: &Substats) -> String {
+	let parts: Vec<String> = SUBSTAT_LABELS.iter()
+		.zip(substats)
+		.filter(|x| *x.1 != 0)
+		.map(|(label, rolls)| format!("{}x {}", rolls, label))
+		.collect();
+	parts.join(" / ")
+}
+
+// DPS only means something for timed rotations, the others keep their old columns
+fn has_dps(ranking: &WeaponRanking) -> bool {
+	ranking.weapons.iter().any(|x| x.dps.is_some())
+}
+
+fn dps(x: Option<f32>) -> String {
+	match x {
+		Some(x) => (x as usize).to_string(),
+		None => "-".to_string()
+	}
+}
+
+// What goes in the Comments column, empty unless the build had an ER to reach
+fn comment(weapon: &RankedWeapon) -> String {
+	match weapon.min_er {
+		Some(x) if weapon.dpr == 0.0 => format!("can't reach {:.1}% ER", x),
+		Some(x) => format!("ER >= {:.1}%", x),
+		None => String::new()
+	}
+}
+
+// The set goes first when the search picked one
+fn comments(weapon: &RankedWeapon) -> String {
+	let comments: Vec<String> = weapon.set.iter().cloned()
+		.chain(Some(comment(weapon)).filter(|x| !x.is_empty()))
+		.collect();
+	comments.join(", ")
+}
+
+// Sets only get a column when the search tried several
+fn has_sets(ranking: &WeaponRanking) -> bool {
+	ranking.weapons.iter().any(|x| x.set.is_some())
+}
+
+// Published numbers have to say how the substats were rolled
+fn standard(ranking: &WeaponRanking) -> String {
+	format!("\nSubstats: {}\n", ranking.standard)
+}
+
+// The fixed-width table we've always been printing, the reference column
+// grows with the name of the reference
+pub fn table(ranking: &WeaponRanking) -> String {
+	let reference = format!("% of {}", ranking.reference);
+	let width = reference.len().max(10);
+	if has_dps(ranking) {
+		let mut out = format!(
+			"{0: <23} {1: <13} {2: <10} {3: <width$} Comments\n",
+			"Weapon", "DPR", "DPS", reference
+		);
+		for weapon in &ranking.weapons {
+			out += &format!(
+				"{0: <23} {1: <13} {2: <10} {3: <width$} {4}\n",
+				weapon.name, weapon.dpr as usize, dps(weapon.dps), of_reference(weapon.of_reference), comments(weapon)
+			);
+		}
+		return out + &standard(ranking);
+	}
+
+	let mut out = format!(
+		"{0: <23} {1: <13} {2: <width$} Comments\n",
+		"Weapon", "DPR", reference
+	);
+	for weapon in &ranking.weapons {
+		out += &format!(
+			"{0: <23} {1: <13} {2: <width$} {3}\n",
+			weapon.name, weapon.dpr as usize, of_reference(weapon.of_reference), comments(weapon)
+		);
+	}
+	out + &standard(ranking)
+}
+
+fn csv_field(field: &str) -> String {
+	if field.contains([',', '"', '\n']) {
+		format!("\"{}\"", field.replace('"', "\"\""))
+	} else {
+		field.to_string()
+	}
+}
+
+// One column per mainstat and substat so it's easy to filter in a spreadsheet
+pub fn csv(ranking: &WeaponRanking) -> String {
+	let with_dps = has_dps(ranking);
+	let with_sets = has_sets(ranking);
+	let mut header = vec!["Weapon".to_string()];
+	if with_sets {
+		header.push("Set".to_string());
+	}
+	header.push("DPR".to_string());
+	if with_dps {
+		header.push("DPS".to_string());
+	}
+	header.push(format!("% of {}", ranking.reference));
+	header.extend([Slot::Sands, Slot::Goblet, Slot::Circlet].map(|x| format!("Main {}", x.name())));
+	header.extend(SUBSTAT_LABELS.iter().map(|x| format!("Rolls {}", x)));
+	header.push("Substats".to_string());
+
+	let mut out = header.iter().map(|x| csv_field(x)).collect::<Vec<_>>().join(",") + "\n";
+	for weapon in &ranking.weapons {
+		let mut row = vec![csv_field(&weapon.name)];
+		if with_sets {
+			row.push(csv_field(weapon.set.as_deref().unwrap_or_default()));
+		}
+		row.push(weapon.dpr.round().to_string());
+		if with_dps {
+			row.push(weapon.dps.map(|x| x.round().to_string()).unwrap_or_default());
+		}
+		row.push(weapon.of_reference.map(|x| format!("{:.1}", x)).unwrap_or_default());
+		row.extend(weapon.build.artifacts.pieces()[2..].iter().map(|x| x.main_stat.to_string()));
+		row.extend(weapon.build.substats.iter().map(|x| x.to_string()));
+		row.push(csv_field(&ranking.standard));
+		out += &(row.join(",") + "\n");
+	}
+	out
+}
+
+pub fn json(ranking: &WeaponRanking) -> Result<String, String> {
+	serde_json::to_string_pretty(ranking)
+		.map(|x| x + "\n")
+		.map_err(|e| format!("couldn't write the ranking as JSON: {}", e))
+}
+
+// Ready to be pasted into a guide
+pub fn markdown(ranking: &WeaponRanking) -> String {
+	let with_dps = has_dps(ranking);
+	let with_sets = has_sets(ranking);
+	let (set_header, set_align) = match with_sets {
+		true => (" Set |", "---|"),
+		false => ("", "")
+	};
+	let mut out = match with_dps {
+		true => format!("| Weapon |{} DPR | DPS | % of {} | Mainstats | Substat rolls |\n|---|{}---:|---:|---:|---|---|\n", set_header, ranking.reference, set_align),
+		false => format!("| Weapon |{} DPR | % of {} | Mainstats | Substat rolls |\n|---|{}---:|---:|---|---|\n", set_header, ranking.reference, set_align)
+	};
+	for weapon in &ranking.weapons {
+		let dpr = match with_dps {
+			true => format!("{} | {}", weapon.dpr as usize, dps(weapon.dps)),
+			false => (weapon.dpr as usize).to_string()
+		};
+		let name = weapon.name.replace('|', "\\|");
+		let name = match with_sets {
+			true => format!("{} | {}", name, weapon.set.as_deref().unwrap_or_default()),
+			false => name
+		};
+		out += &format!(
+			"| {} | {} | {} | {} | {} |\n",
+			name,
+			dpr,
+			of_reference(weapon.of_reference),
+			weapon.build.artifacts,
+			describe_substats(&weapon.build.substats)
+		);
+	}
+	out + &standard(ranking)
+}
+
+// Everything about a single build, what used to be printed for the optimal one
+pub fn build_sheet(weapon: &RankedWeapon, standard: &str) -> String {
+	let mut out = format!("{}\n\n", weapon.name);
+	if let Some(set) = &weapon.set {
+		out += &format!("ARTIFACT SET: {}\n\n", set);
+	}
+	out += "OPTIMAL ARTIFACTS:\n";
+	for piece in weapon.build.artifacts.pieces() {
+		out += &format!("{}\t{}\n", piece.slot.name(), describe_piece(piece));
+	}
+	out += &format!("\nOPTIMAL ARTIFACT SUBSTAT ROLLS ({}):\n", standard);
+	for (label, rolls) in SUBSTAT_LABELS.iter().zip(&weapon.build.substats) {
+		out += &format!("{}\t{}\n", label, rolls);
+	}
+	out += "\nACTIVE BUFFS:\n";
+	for buff in &weapon.buffs {
+		out += &format!("{:?}\t{}", buff.source, buff.name);
+		if buff.uptime < 1.0 {
+			out += &format!(" ({}% uptime)", buff.uptime * 100.0);
+		}
+		out += "\n";
+	}
+	out += &format!("\nSTATS WITH BUFFS:\n{}\n", weapon.stats);
+	out += &format!("DPR: {}\n", weapon.dpr);
+	if let Some(dps) = weapon.dps {
+		out += &format!("DPS: {}\n", dps);
+	}
+	let comment = comment(weapon);
+	if !comment.is_empty() {
+		out += &format!("{}\n", comment);
+	}
+	out
+}
+
+#[cfg(test)]
+mod tests {
+	use serde_json::Value;
+	use super::*;
+	use crate::artifacts::{ArtifactBuild, ArtifactStat};
+	use crate::calculators::Build;
+	use crate::characters::{self, Level};
+
+	fn ranking() -> WeaponRanking {
+		let weapon = |name: &str, dpr: f32, of_reference: f32| RankedWeapon {
+			name: name.to_string(),
+			dpr,
+			dps: None,
+			min_er: Some(120.0),
+			set: None,
+			build: Build {
+				artifacts: ArtifactBuild::layout(ArtifactStat::Em, ArtifactStat::ElementalDmg, ArtifactStat::CritDamage).unwrap(),
+				substats: [0, 2, 4, 6, 8, 0, 0],
+			},
+			stats: characters::FRAUD.base(Level::MAX).unwrap(),
+			buffs: Vec::new(),
+			of_reference: Some(of_reference),
+		};
+		WeaponRanking {
+			reference: "Sun R1".to_string(),
+			standard: "KQMS, 2 fixed rolls".to_string(),
+			weapons: vec![weapon("Sun R1", 1000.0, 100.0), weapon("Serpent \"Spine\", R5", 950.4, 95.04)],
+		}
+	}
+
+	// Just enough of CSV for what we write: quoted fields with doubled quotes
+	fn csv_rows(text: &str) -> Vec<Vec<String>> {
+		text.lines().map(|line| {
+			let mut fields = vec![String::new()];
+			let mut quoted = false;
+			let mut chars = line.chars().peekable();
+			while let Some(c) = chars.next() {
+				match c {
+					'"' if quoted && chars.peek() == Some(&'"') => { chars.next(); fields.last_mut().unwrap().push('"') }
+					'"' => quoted = !quoted,
+					',' if !quoted => fields.push(String::new()),
+					c => fields.last_mut().unwrap().push(c)
+				}
+			}
+			fields
+		}).collect()
+	}
+
+	#[test]
+	fn json_round_trip() {
+		let ranking = ranking();
+		let value: Value = serde_json::from_str(&json(&ranking).unwrap()).unwrap();
+		assert_eq!(value["reference"], "Sun R1");
+		assert_eq!(value["standard"], ranking.standard.as_str());
+		for (weapon, parsed) in ranking.weapons.iter().zip(value["weapons"].as_array().unwrap()) {
+			assert_eq!(parsed["name"], weapon.name.as_str());
+			assert_eq!(parsed["dpr"].as_f64().unwrap() as f32, weapon.dpr);
+			assert_eq!(parsed["min_er"].as_f64().unwrap() as f32, 120.0);
+			let substats: Vec<usize> = serde_json::from_value(parsed["build"]["substats"].clone()).unwrap();
+			assert_eq!(substats, weapon.build.substats);
+			let pieces = parsed["build"]["artifacts"]["pieces"].as_array().unwrap();
+			let slots: Vec<&str> = pieces.iter().map(|x| x["slot"].as_str().unwrap()).collect();
+			assert_eq!(slots, ["Flower", "Plume", "Sands", "Goblet", "Circlet"]);
+			assert_eq!(pieces[2]["main_stat"], "Em");
+		}
+	}
+
+	#[test]
+	fn build_sheet_lists_the_pieces() {
+		let mut weapon = ranking().weapons.remove(0);
+		let mut pieces = weapon.build.artifacts.pieces().to_vec();
+		pieces[4] = Artifact::new(Slot::Circlet, None, ArtifactStat::CritDamage, vec![(ArtifactStat::CritRate, 5), (ArtifactStat::Atk, 2)]).unwrap();
+		weapon.build.artifacts = ArtifactBuild::new(pieces).unwrap();
+		let sheet = build_sheet(&weapon, "KQMS");
+		assert!(sheet.contains("flower\tHP\nplume\tATK\nsands\tEM\ngoblet\tBONUS%\ncirclet\tCD, 5x CR / 2x ATK\n"), "{}", sheet);
+	}
+
+	#[test]
+	fn csv_round_trip() {
+		let ranking = ranking();
+		let rows = csv_rows(&csv(&ranking));
+		assert_eq!(rows.len(), 1 + ranking.weapons.len());
+		assert_eq!(rows[0][..3], ["Weapon", "DPR", "% of Sun R1"]);
+		assert!(rows.iter().all(|x| x.len() == rows[0].len()));
+		for (weapon, row) in ranking.weapons.iter().zip(&rows[1..]) {
+			assert_eq!(row[0], weapon.name);
+			assert_eq!(row[1], weapon.dpr.round().to_string());
+			assert_eq!(row[2], format!("{:.1}", weapon.of_reference.unwrap()));
+			assert_eq!(row[3..6], ["EM", "BONUS%", "CD"]);
+			let substats: Vec<usize> = row[6..13].iter().map(|x| x.parse().unwrap()).collect();
+			assert_eq!(substats, weapon.build.substats);
+			assert_eq!(row[13], ranking.standard);
+		}
+	}
+}
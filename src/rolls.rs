@@ -0,0 +1,316 @@
+use std::cmp::Reverse;
+use std::fmt;
+
+use crate::*;
+use crate::artifacts::{Artifact, ArtifactBuild, ArtifactStat, Slot};
+
+// Substat rolls the way they happen on actual pieces. Every piece starts
+// with 4 substats and gets 5 upgrades on the way to 20, each one worth 70,
+// 80, 90 or 100% of the max roll, and never into the piece's own mainstat.
+
+pub const STARTING_SUBSTATS: usize = 4;
+pub const UPGRADES: usize = 5;
+pub const ROLLS_PER_PIECE: usize = STARTING_SUBSTATS + UPGRADES;
+// The starting roll and every upgrade on the same substat
+pub const ROLLS_PER_SUBSTAT: usize = 1 + UPGRADES;
+pub const TOTAL_ROLLS: usize = ROLLS_PER_PIECE * Slot::ALL.len();
+
+// The stats `Substats` counts rolls into, in the same order
+pub const SUBSTAT_ORDER: [ArtifactStat; 7] = [
+	ArtifactStat::HpPercent,
+	ArtifactStat::AtkPercent,
+	ArtifactStat::Em,
+	ArtifactStat::CritRate,
+	ArtifactStat::CritDamage,
+	ArtifactStat::DefPercent,
+	ArtifactStat::EnergyRecharge,
+];
+
+// Max roll of a 5* substat
+pub fn max_roll(stat: ArtifactStat) -> f32 {
+	match stat {
+		ArtifactStat::Hp => 298.75,
+		ArtifactStat::Atk => 19.45,
+		ArtifactStat::Def => 23.15,
+		ArtifactStat::HpPercent | ArtifactStat::AtkPercent => 5.83,
+		ArtifactStat::DefPercent => 7.29,
+		ArtifactStat::Em => 23.31,
+		ArtifactStat::EnergyRecharge => 6.48,
+		ArtifactStat::CritRate => 3.89,
+		ArtifactStat::CritDamage => 7.77,
+		// Never substats
+		ArtifactStat::ElementalDmg | ArtifactStat::Healing => 0.0,
+	}
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum RollTier {
+	Low,
+	Mid,
+	High,
+	Max,
+}
+
+impl RollTier {
+	pub const ALL: [RollTier; 4] = [RollTier::Low, RollTier::Mid, RollTier::High, RollTier::Max];
+
+	// Share of the max roll
+	pub fn share(&self) -> f32 {
+		match self {
+			RollTier::Low => 0.7,
+			RollTier::Mid => 0.8,
+			RollTier::High => 0.9,
+			RollTier::Max => 1.0,
+		}
+	}
+
+	// All four are as likely, so a roll is worth 85% on average
+	pub fn average() -> f32 {
+		RollTier::ALL.iter().map(|x| x.share()).sum::<f32>() / RollTier::ALL.len() as f32
+	}
+}
+
+// Pieces of the build that can roll `stat`, i.e. don't have it as their mainstat
+fn pieces_without(stat: ArtifactStat, build: &ArtifactBuild) -> usize {
+	build.pieces().iter().filter(|x| x.main_stat != stat).count()
+}
+
+// Most rolls `stat` can get over the five pieces, if nothing else wanted them
+pub fn cap(stat: ArtifactStat, build: &ArtifactBuild) -> usize {
+	ROLLS_PER_SUBSTAT * pieces_without(stat, build)
+}
+
+// Rolls `stat` can still get on a piece. Each of its 4 starting substats
+// takes a roll whether we want it or not, so with n of them picked the piece
+// only has room for 5 + n rolls into those.
+fn room(piece: &Artifact, stat: ArtifactStat) -> usize {
+	if piece.main_stat == stat {
+		return 0;
+	}
+	let picked = piece.substats.len();
+	let total: usize = piece.substats.iter().map(|x| x.1).sum();
+	match piece.substats.iter().find(|x| x.0 == stat) {
+		Some(x) => (ROLLS_PER_SUBSTAT - x.1).min(UPGRADES + picked - total),
+		None if picked < STARTING_SUBSTATS => ROLLS_PER_SUBSTAT.min(UPGRADES + picked + 1 - total),
+		None => 0
+	}
+}
+
+// What a build's substats add up to, given where its rolls went
+#[derive(Clone, Debug, PartialEq)]
+pub struct RollModel {
+	// Share of the max roll every roll is worth
+	pub share: f32,
+	// Rolls into flat [HP, ATK, DEF] every build ends up with
+	pub flat: [usize; 3],
+	// Rolls the search hands out
+	pub rolls: usize,
+}
+
+impl RollModel {
+	// Average rolls, and one of the 4 substats of every piece is flat: two
+	// pieces with flat HP, two with flat ATK and one with flat DEF. 30 of the
+	// other 40 rolls land where the build wants them.
+	pub fn realistic() -> Self {
+		RollModel { share: RollTier::average(), flat: [2, 2, 1], rolls: 30 }
+	}
+
+	pub fn check(&self) -> Result<(), String> {
+		if self.rolls > self.budget() {
+			return Err(format!(
+				"{} rolls is more than the {} the five pieces have besides the flat ones",
+				self.rolls, self.budget()
+			));
+		}
+		Ok(())
+	}
+
+	// Rolls left to hand out once the flat ones took theirs
+	pub fn budget(&self) -> usize {
+		TOTAL_ROLLS.saturating_sub(self.flat.iter().sum())
+	}
+
+	pub fn value(&self, stat: ArtifactStat) -> f32 {
+		max_roll(stat) * self.share
+	}
+
+	// Rolls past the cap of a stat are lost. Stat by stat that's the most
+	// `placed` could keep, and damage never goes down with one more roll, so
+	// it's what bounds the search.
+	pub fn capped(&self, rolls: &Substats, build: &ArtifactBuild) -> Substats {
+		let mut capped = *rolls;
+		for (rolls, stat) in capped.iter_mut().zip(SUBSTAT_ORDER) {
+			*rolls = (*rolls).min(cap(stat, build));
+		}
+		capped
+	}
+
+	// The flat rolls and then `rolls` handed out to the pieces one at a time,
+	// each to whichever piece has the most room left for it, the one with the
+	// fewest substats on a tie. Stats with the fewest pieces to go on come
+	// first, and whatever doesn't fit is lost.
+	pub fn place(&self, rolls: &Substats, build: &ArtifactBuild) -> ArtifactBuild {
+		let mut wanted: Vec<(ArtifactStat, usize)> = SUBSTAT_ORDER.into_iter().zip(*rolls).collect();
+		wanted.sort_by_key(|x| (pieces_without(x.0, build), Reverse(x.1)));
+		let flat = [ArtifactStat::Hp, ArtifactStat::Atk, ArtifactStat::Def].into_iter().zip(self.flat);
+
+		let mut pieces: Vec<Artifact> = build.pieces().to_vec();
+		for piece in &mut pieces {
+			piece.substats.clear();
+		}
+		for (stat, rolls) in flat.chain(wanted) {
+			for _ in 0..rolls {
+				let best = (0..pieces.len()).max_by_key(|&i| (room(&pieces[i], stat), Reverse(pieces[i].substats.len()), Reverse(i))).unwrap();
+				if room(&pieces[best], stat) == 0 {
+					break;
+				}
+				let piece = &mut pieces[best];
+				match piece.substats.iter_mut().find(|x| x.0 == stat) {
+					Some(x) => x.1 += 1,
+					None => piece.substats.push((stat, 1)),
+				}
+			}
+		}
+		ArtifactBuild::new(pieces).unwrap()
+	}
+
+	// What of `rolls` made it onto the pieces
+	pub fn placed(&self, rolls: &Substats, build: &ArtifactBuild) -> Substats {
+		self.place(rolls, build).substats()
+	}
+
+	// Flat [HP, ATK, DEF] the flat rolls are worth
+	pub fn flat_stats(&self) -> [f32; 3] {
+		let stats = [ArtifactStat::Hp, ArtifactStat::Atk, ArtifactStat::Def];
+		let mut out = [0.0; 3];
+		for ((out, stat), rolls) in out.iter_mut().zip(stats).zip(self.flat) {
+			*out = rolls as f32 * self.value(stat);
+		}
+		out
+	}
+}
+
+// The KQM standard: 2 fixed rolls of every substat, flat ones included, then
+// 20 liquid rolls with at most 2 per piece that doesn't have the stat as its
+// mainstat. Every roll is worth the average of the four tiers.
+pub const KQMS_FIXED: usize = 2;
+pub const KQMS_LIQUID: usize = 20;
+pub const KQMS_LIQUID_PER_PIECE: usize = 2;
+
+// Liquid rolls into `stat` that count under KQMS
+pub fn kqms_cap(stat: ArtifactStat, build: &ArtifactBuild) -> usize {
+	KQMS_LIQUID_PER_PIECE * pieces_without(stat, build)
+}
+
+// How substat rolls turn into stats. Published numbers should say which one
+// they follow.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum RollPolicy {
+	// See KQMS_FIXED and the rest above
+	#[default]
+	Kqms,
+	// What we used to do: 2 fixed rolls of every substat and then any number
+	// of rolls anywhere, no caps
+	Raw { rolls: usize },
+	// Pieces as in game, as much or as little as the model says
+	Custom(RollModel),
+}
+
+impl RollPolicy {
+	// Rolls the search hands out
+	pub fn rolls(&self) -> usize {
+		match self {
+			RollPolicy::Kqms => KQMS_LIQUID,
+			RollPolicy::Raw { rolls } => *rolls,
+			RollPolicy::Custom(model) => model.rolls,
+		}
+	}
+
+	// What of `rolls` counts once they're on the pieces. Only custom rolls
+	// go on pieces, KQMS has its own caps and raw rolls have none.
+	pub fn placed(&self, rolls: &Substats, build: &ArtifactBuild) -> Substats {
+		match self {
+			RollPolicy::Custom(model) => model.placed(rolls, build),
+			_ => *rolls
+		}
+	}
+
+	// The pieces with the rolls on them, if the policy puts them on pieces
+	pub fn place(&self, rolls: &Substats, build: &ArtifactBuild) -> ArtifactBuild {
+		match self {
+			RollPolicy::Custom(model) => model.place(rolls, build),
+			_ => build.clone()
+		}
+	}
+}
+
+impl fmt::Display for RollPolicy {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			RollPolicy::Kqms => write!(
+				f, "KQMS, {} fixed rolls of every substat and {} liquid rolls",
+				KQMS_FIXED, KQMS_LIQUID
+			),
+			RollPolicy::Raw { rolls } => write!(
+				f, "raw, {} fixed rolls of every substat and {} uncapped rolls",
+				KQMS_FIXED, rolls
+			),
+			RollPolicy::Custom(model) => write!(
+				f, "custom, {} of {} rolls at {}% with {}/{}/{} flat HP/ATK/DEF rolls",
+				model.rolls, model.budget(), (model.share * 100.0).round(), model.flat[0], model.flat[1], model.flat[2]
+			),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn caps_follow_the_mainstats() {
+		// Double EM + CR: three pieces left to roll EM, four for CR
+		let build = ArtifactBuild::layout(ArtifactStat::Em, ArtifactStat::Em, ArtifactStat::CritRate).unwrap();
+		assert_eq!(cap(ArtifactStat::Em, &build), 18);
+		assert_eq!(cap(ArtifactStat::CritRate, &build), 24);
+		assert_eq!(cap(ArtifactStat::CritDamage, &build), 30);
+		assert_eq!(cap(ArtifactStat::Hp, &build), 24);
+
+		let model = RollModel::realistic();
+		assert_eq!(model.capped(&[0, 0, 20, 30, 30, 0, 0], &build), [0, 0, 18, 24, 30, 0, 0]);
+		assert_eq!(model.budget(), 40);
+		assert!((RollTier::average() - 0.85).abs() < 1e-6);
+		assert!((model.value(ArtifactStat::CritRate) - 3.3065).abs() < 1e-4);
+	}
+
+	#[test]
+	fn rolls_fit_on_the_pieces() {
+		// Crit only: the CR circlet rolls 6 CD at most, the other four pieces
+		// 7 crit each next to their flat substat and one more
+		let build = ArtifactBuild::layout(ArtifactStat::AtkPercent, ArtifactStat::ElementalDmg, ArtifactStat::CritRate).unwrap();
+		let model = RollModel { rolls: 40, ..RollModel::realistic() };
+		let placed = model.placed(&[0, 0, 0, 20, 20, 0, 0], &build);
+		assert_eq!(placed[3] + placed[4], 34);
+		assert!(model.capped(&[0, 0, 0, 20, 20, 0, 0], &build) == [0, 0, 0, 20, 20, 0, 0]);
+
+		// A spread that fits loses nothing
+		assert_eq!(model.placed(&[0, 6, 8, 8, 8, 0, 0], &build), [0, 6, 8, 8, 8, 0, 0]);
+		for piece in model.place(&[4, 4, 8, 8, 8, 4, 4], &build).pieces() {
+			assert!(Artifact::new(piece.slot, None, piece.main_stat, piece.substats.clone()).is_ok(), "{:?}", piece);
+		}
+		// Two substats only ever get the five upgrades on top of their own roll
+		let flower = Artifact::new(Slot::Flower, None, ArtifactStat::Hp, vec![(ArtifactStat::CritRate, 6), (ArtifactStat::CritDamage, 3)]);
+		assert!(flower.is_err());
+	}
+
+	#[test]
+	fn kqms_caps_liquid_rolls_per_piece() {
+		// EM sands, EM goblet: 3 pieces left, so 6 liquid EM rolls
+		let build = ArtifactBuild::layout(ArtifactStat::Em, ArtifactStat::Em, ArtifactStat::CritRate).unwrap();
+		assert_eq!(kqms_cap(ArtifactStat::Em, &build), 6);
+		assert_eq!(kqms_cap(ArtifactStat::CritRate, &build), 8);
+		assert_eq!(kqms_cap(ArtifactStat::CritDamage, &build), 10);
+		assert_eq!(RollPolicy::Kqms.rolls(), 20);
+		assert!(RollModel { rolls: 41, ..RollModel::realistic() }.check().is_err());
+	}
+}
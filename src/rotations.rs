@@ -1,47 +1,116 @@
 use crate::*;
+use crate::artifacts::ArtifactBuild;
+use crate::damage::*;
+use crate::buffs::{self, ActiveBuff, BoxedBuff, Buff, Source};
+use crate::characters::{self, Character, Config};
+use crate::energy::{Energy, Particles};
+use crate::enemies::Enemy;
+use crate::gauge::{self, Event};
+use crate::rolls::RollPolicy;
+use crate::timeline::{Step, TimedHit, Timeline, Window};
+use crate::weapons::Conditions;
+
+pub type BoxedRotation = Box<dyn Fn(&ArtifactBuild, &Substats, &RollPolicy, &dyn Fn(CharStats) -> CharStats, &BoxedBuff, &Enemy) -> (f32, CharStats, Vec<ActiveBuff>) + Sync>;
 
 /////////////////////////////
-///////// Common ////////////
+//////// Registry ///////////
 /////////////////////////////
 
-// The damage formula expressed as a multivariate function
-fn damage(
-	base_dmg: f32,
-	base_dmg_multiplier: f32,
-	additive_dmg_bonus: f32,
-	dmg_bonus: f32,
-	crit_rate: f32,
-	crit_damage: f32,
-	res_shred: f32,
-	amplifying_reaction: f32
-) -> f32 {
-	// Effective crit multiplier evaluated as n - number of hits, approaches infinity
-	let crit = 1.0 + (crit_rate / 100.0).clamp(0.0, 1.0) * crit_damage / 100.0;
+pub const BUILTIN: [&str; 5] = [
+	"shark_furina_thoma_kazuha",
+	"shark_furina_thoma_nahida",
+	"shark_furina_sige_kazuha",
+	"shark_yelan_xl_zhong",
+	"fraud_yelan_furina_xilonen",
+];
 
-	// Assume we're fighting Masanori lvl. 90 if this switch is on
-	let masanori = true;
+// A built-in rotation, ready for the calculators
+pub struct Builtin {
+	pub character: &'static Character,
+	pub rotation: BoxedRotation,
+	// The whole team's field time in s
+	pub length: f32,
+	// Lowest ER in % that gets the burst back every rotation
+	pub min_er: Option<f32>,
+}
 
-	let (enemy_def_multiplier, mut enemy_res_multiplier) = match masanori {
-		true => (0.48, 0.9),
-		false => (1.0, 1.0)
+// The team buffs are checked once here, not on every evaluation
+pub fn by_name(name: &str, config: Config) -> Option<Result<Builtin, String>> {
+	let builtin = match name {
+		"shark_furina_thoma_kazuha" => shark_furina_thoma_kazuha(&config),
+		"shark_furina_thoma_nahida" => shark_furina_thoma_nahida(&config),
+		"shark_furina_sige_kazuha" => shark_furina_sige_kazuha(&config),
+		"shark_yelan_xl_zhong" => shark_yelan_xl_zhong(&config),
+		"fraud_yelan_furina_xilonen" => fraud_yelan_furina_xilonen(&config),
+		_ => return None
 	};
+	Some(builtin.map_err(|e| format!("{}: {}", name, e)))
+}
 
-	let over_shred = enemy_res_multiplier + res_shred - 1.0;
-	if over_shred > 0.0 {
-		enemy_res_multiplier = 1.0 + over_shred * 0.5;
-	} else {
-		enemy_res_multiplier += res_shred;
+// The character's own actions have to fit in the team's field time
+fn builtin(character: &'static Character, config: &Config, timeline: Timeline, length: f32, energy: Option<Energy>) -> Result<Builtin, String> {
+	if timeline.length() > length {
+		return Err(format!("the last action ends at {}s, past the {}s rotation", timeline.length(), length));
 	}
+	let min_er = match energy {
+		Some(energy) => energy.min_er(character)?,
+		None => None
+	};
+	// Base stats only depend on the config, no need to redo them per build
+	let base_stats = character.base(config.level)?;
+	Ok(Builtin {
+		character,
+		rotation: Box::new(move |a, s, p, base, buff, enemy| timeline.run(base_stats, base, buff, a, s, p, enemy)),
+		length,
+		min_er,
+	})
+}
 
-	(base_dmg * base_dmg_multiplier + additive_dmg_bonus) *
-	(1.0 + dmg_bonus) * crit * enemy_def_multiplier *
-	enemy_res_multiplier * amplifying_reaction
+fn step(name: &str, start: f32, duration: f32, cooldown: f32) -> Step {
+	Step { name: name.to_string(), start, duration, cooldown }
 }
 
-fn forward_vape_multiplier(
-	trigger: &CharStats
-) -> f32 {
-	2.0 * (1.0 + (2.78 * trigger.em) / (1400.0 + trigger.em) + trigger.reaction_bonus)
+fn window(buff: impl Buff + 'static, start: f32, duration: f32) -> Window {
+	Window { buff: buff.boxed(), start, duration }
+}
+
+type ReactingDamage = Box<dyn Fn(&CharStats, Reaction, &Enemy) -> f32 + Send + Sync>;
+
+// A hit before the gauge has had its say, it gets whatever reaction it triggered
+struct Hit {
+	time: f32,
+	// ICD tag, None for hits that always apply their element
+	icd: Option<&'static str>,
+	damage: ReactingDamage,
+}
+
+fn hit(time: f32, icd: Option<&'static str>, damage: impl Fn(&CharStats, Reaction, &Enemy) -> f32 + Send + Sync + 'static) -> Hit {
+	Hit { time, icd, damage: Box::new(damage) }
+}
+
+// Same as rotation files: the team's applications go through the gauge
+// first, so they win ties, then the character's hits at 1U each
+fn react(element: Element, applications: Vec<Event>, hits: Vec<Hit>) -> Vec<TimedHit> {
+	let own = applications.len();
+	let events: Vec<Event> = applications.into_iter()
+		.chain(hits.iter().map(|x| Event { time: x.time, element, units: 1.0, icd: x.icd.map(str::to_string) }))
+		.collect();
+	let reactions = gauge::simulate(&events);
+	hits.into_iter()
+		.zip(reactions[own..].iter().copied())
+		.map(|(hit, reaction)| TimedHit {
+			time: hit.time,
+			damage: Box::new(move |stats: &CharStats, enemy: &Enemy| (hit.damage)(stats, reaction, enemy))
+		})
+		.collect()
+}
+
+// The set the rotation was written with, unless the config picks another one
+fn set(config: &Config, own: Vec<BoxedBuff>) -> Vec<BoxedBuff> {
+	match config.set {
+		Some(choice) => choice.buffs(&Conditions::default()).expect("sets take their own defaults"),
+		None => own
+	}
 }
 
 /////////////////////////////
@@ -50,173 +119,213 @@ fn forward_vape_multiplier(
 
 pub fn shark_na_bite(
 	shark: &CharStats,
+	config: &Config,
 	momentum: usize,
-	vape: bool
+	reaction: Reaction,
+	enemy: &Enemy
 ) -> f32 {
-	let mut wave_bonus = momentum as f32 * 0.078 * shark.hp;
-	let na_multiplier = 15.6 / 100.0;
-	let vape_multiplier = match vape {
-		true => forward_vape_multiplier(&shark),
-		false => 1.0
-	};
+	let mut wave_bonus = momentum as f32 * characters::SHARK.multiplier("wave_momentum", config) / 100.0 * shark.hp;
+	let na_multiplier = characters::SHARK.multiplier("bite", config) / 100.0;
 
 	if momentum == 3 {
-		wave_bonus += 0.391 * shark.hp;
+		wave_bonus += characters::SHARK.multiplier("puffer_bonus", config) / 100.0 * shark.hp;
 	}
 
 	damage(
+		shark,
+		Element::Hydro,
 		shark.hp * na_multiplier + shark.na_bonus_flat,
-		1.0,
 		wave_bonus,
-		(shark.dmg_bonus + shark.na_bonus) / 100.0,
-		shark.crit_rate,
-		shark.crit_damage,
-		shark.res_shred / 100.0,
-		vape_multiplier
+		shark.na_bonus,
+		reaction,
+		enemy
 	)
 }
 
 pub fn shark_burst(
 	shark: &CharStats,
-	vape: bool
+	config: &Config,
+	reaction: Reaction,
+	enemy: &Enemy
 ) -> f32 {
-	let burst_multiplier = 105.2 / 100.0;
-	let vape_multiplier = match vape {
-		true => forward_vape_multiplier(&shark),
-		false => 1.0
-	};
+	let burst_multiplier = characters::SHARK.multiplier("burst", config) / 100.0;
 
 	damage(
+		shark,
+		Element::Hydro,
 		shark.hp * burst_multiplier,
-		1.0,
 		0.0,
-		(shark.dmg_bonus) / 100.0,
-		shark.crit_rate,
-		shark.crit_damage,
-		shark.res_shred / 100.0,
-		vape_multiplier
+		0.0,
+		reaction,
+		enemy
+	)
+}
+
+pub fn shark_c1_bite(
+	shark: &CharStats,
+	config: &Config,
+	reaction: Reaction,
+	enemy: &Enemy
+) -> f32 {
+	let c1_multiplier = characters::SHARK.multiplier("c1_bite", config) / 100.0;
+
+	damage(
+		shark,
+		Element::Hydro,
+		shark.hp * c1_multiplier,
+		0.0,
+		shark.na_bonus,
+		reaction,
+		enemy
 	)
 }
 
-/// Assuming 4 vaped normals, 3 of which are enhanced to max stacks.
-/// Wrapped up with burst.
-pub fn shark_vape(stats: &CharStats) -> f32 {
-	// The duration of her skill seems to be around 6s idfk
-	// Just assume she bites two times after applying 3 stacks each time
-	let mut damage = 0.0;
-	damage += shark_na_bite(&stats, 1, true);
-	damage += shark_na_bite(&stats, 3, true);
-	damage += shark_na_bite(&stats, 3, true);
-	damage += shark_na_bite(&stats, 3, true);
-
-	// Use the burst either as an opening move
-	// or as a finisher, depending which is better
-	let burst = shark_burst(&stats, true);
-	damage += burst;
-	damage
+/// 4 normals, 3 of which are enhanced to max stacks. Wrapped up with burst.
+/// Mualani comes on once the team is set up at 5s, and every bite applies
+/// hydro, so they vaporize as long as the team keeps pyro up.
+fn shark_vape(config: &Config) -> (Vec<Step>, Vec<Hit>) {
+	let config = *config;
+	let steps = vec![
+		step("skill", 5.0, 0.5, 10.0),
+		step("bite", 5.5, 1.5, 0.0),
+		step("bite", 7.0, 1.5, 0.0),
+		step("bite", 8.5, 1.5, 0.0),
+		step("bite", 10.0, 1.5, 0.0),
+		step("burst", 11.5, 1.5, 15.0),
+	];
+	// C1 adds onto the first bite at max stacks, and reacts along with it
+	let c1 = characters::SHARK.hit("c1_bite", &config).is_some();
+	let hits = vec![
+		hit(5.5, None, move |stats, reaction, enemy| shark_na_bite(stats, &config, 1, reaction, enemy)),
+		hit(7.0, None, move |stats, reaction, enemy| {
+			let c1 = if c1 { shark_c1_bite(stats, &config, reaction, enemy) } else { 0.0 };
+			shark_na_bite(stats, &config, 3, reaction, enemy) + c1
+		}),
+		hit(8.5, None, move |stats, reaction, enemy| shark_na_bite(stats, &config, 3, reaction, enemy)),
+		hit(10.0, None, move |stats, reaction, enemy| shark_na_bite(stats, &config, 3, reaction, enemy)),
+		hit(11.5, Some("burst"), move |stats, reaction, enemy| shark_burst(stats, &config, reaction, enemy)),
+	];
+	(steps, hits)
 }
 
-pub fn shark_furina_thoma_kazuha(
-	mainstats: &[f32; 6],
-	substats: &[usize; 5],
-	base: impl Fn(CharStats) -> CharStats,
-	buff: impl Fn(CharStats, CharStats) -> CharStats
-) -> f32 {
-	let stats1 = stats(
-		characters::SHARK,
-		&base,								// This is the weapon base stat function
-		vec![								// This is a list of all the dynamic buffs
-			&buff,
-			&buffs::obsidian,
-			&buffs::kazuha_e,
-			&buffs::vv_shred,
-			&buffs::thoma_c6,
-			&buffs::hydro_resonance,
-			&buffs::furina_burst(150.0),
-			&buffs::scroll(false)				// Thoma is on scroll
-		],
-		mainstats,
-		substats
-	);
-
-	shark_vape(&stats1)
+// Rough particle counts, what sets the ER the builds need. Enemies are good
+// for a few clear particles on top of the team's.
+fn shark_energy(particles: &[(f32, Option<Element>, bool)]) -> Option<Energy> {
+	Some(Energy {
+		skills: 1.0,
+		particles: [(3.0, None, false)].iter().chain(particles)
+			.map(|&(count, element, off_field)| Particles { count, element, off_field })
+			.collect(),
+		flat: 0.0,
+	})
 }
 
-pub fn shark_furina_thoma_nahida(
-	mainstats: &[f32; 6],
-	substats: &[usize; 5],
-	base: impl Fn(CharStats) -> CharStats,
-	buff: impl Fn(CharStats, CharStats) -> CharStats
-) -> f32 {
-	let stats = stats(
-		characters::SHARK,
-		&base,								// This is the weapon base stat function
-		vec![								// This is a list of all the dynamic buffs
-			&buff,
-			&buffs::tenacity2pc,
-			&buffs::hod2pc,
-			&buffs::nahida_burst(800.0),
-			&buffs::thoma_c6,
-			&buffs::hydro_resonance,
-			&buffs::furina_burst(150.0),
-			&buffs::scroll(false),			// Furina is on scroll
-			&buffs::instructor_share		// Thoma is on instructors
-		],
-		mainstats,
-		substats
-	);
-
-	shark_vape(&stats)
+// Thoma's burst adds pyro every second while Mualani hits
+fn thoma_burst() -> Vec<Event> {
+	gauge::repeated(Element::Pyro, 1.0, 3.0, 1.0, 18.0)
 }
 
-pub fn shark_furina_sige_kazuha(
-	mainstats: &[f32; 6],
-	substats: &[usize; 5],
-	base: impl Fn(CharStats) -> CharStats,
-	buff: impl Fn(CharStats, CharStats) -> CharStats
-) -> f32 {
-	let stats = stats(
-		characters::SHARK,
-		&base,								// This is the weapon base stat function
-		vec![								// This is a list of all the dynamic buffs
-			&buff,
-			&buffs::obsidian,
-			&buffs::hydro_resonance,
-			&buffs::furina_burst(200.0),
-			&buffs::scroll(false),			// Sige is on scroll
-			&buffs::kazuha_e,
-			&buffs::vv_shred
-		],
-		mainstats,
-		substats
-	);
-
-	shark_vape(&stats)
+// Furina, Thoma then Kazuha, whose A4 and shred start with his skill at 4s
+pub fn shark_furina_thoma_kazuha(config: &Config) -> Result<Builtin, String> {
+	let buffs = vec![
+		buffs::thoma_c6().boxed(),
+		buffs::hydro_resonance(&[Element::Hydro, Element::Hydro, Element::Pyro, Element::Anemo]).boxed(),
+		buffs::scroll(&[Element::Pyro, Element::Hydro], false).boxed()	// Thoma is on scroll
+	].into_iter()
+		.chain(set(config, vec![buffs::obsidian().boxed()]))
+		.chain(characters::SHARK.buffs(config))
+		.collect();
+	let windows = vec![
+		window(buffs::furina_burst(150.0), 1.0, 18.0),
+		window(buffs::kazuha_e(Element::Hydro), 4.0, 8.0),
+		window(buffs::vv_shred(Element::Hydro), 4.0, 10.0),
+	];
+
+	let (steps, hits) = shark_vape(config);
+	let hits = react(Element::Hydro, thoma_burst(), hits);
+	// The salon goes off while Mualani is on field
+	let energy = shark_energy(&[
+		(6.0, Some(Element::Hydro), false),
+		(3.5, Some(Element::Pyro), false),
+		(4.0, Some(Element::Anemo), true),
+	]);
+	builtin(&characters::SHARK, config, Timeline::new(&steps, buffs, windows, hits)?, 20.0, energy)
 }
 
-pub fn shark_yelan_xl_zhong(
-	mainstats: &[f32; 6],
-	substats: &[usize; 5],
-	base: impl Fn(CharStats) -> CharStats,
-	buff: impl Fn(CharStats, CharStats) -> CharStats
-) -> f32 {
-	let stats = stats(
-		characters::SHARK,
-		&base,								// This is the weapon base stat function
-		vec![								// This is a list of all the dynamic buffs
-			&buff,
-			&buffs::tenacity2pc,
-			&buffs::hod2pc,
-			&buffs::yelan_a4,
-			&buffs::hydro_resonance,
-			&buffs::petra_share,			// Zhong is on petra
-			&buffs::instructor_share		// Xiangling is on instructors
-		],
-		mainstats,
-		substats
-	);
-
-	shark_vape(&stats)
+pub fn shark_furina_thoma_nahida(config: &Config) -> Result<Builtin, String> {
+	let buffs = vec![
+		buffs::thoma_c6().boxed(),
+		buffs::hydro_resonance(&[Element::Hydro, Element::Hydro, Element::Pyro, Element::Dendro]).boxed(),
+		buffs::scroll(&[Element::Pyro, Element::Hydro], false).boxed(),	// Furina is on scroll
+		buffs::instructor_share().boxed()		// Thoma is on instructors
+	].into_iter()
+		.chain(set(config, vec![buffs::tenacity2pc().boxed(), buffs::hod2pc().boxed()]))
+		.chain(characters::SHARK.buffs(config))
+		.collect();
+	let windows = vec![
+		window(buffs::furina_burst(150.0), 1.0, 18.0),
+		window(buffs::nahida_burst(800.0), 4.0, 15.0),
+	];
+
+	let (steps, hits) = shark_vape(config);
+	let hits = react(Element::Hydro, thoma_burst(), hits);
+	// The salon and Nahida's skill go off while Mualani is on field
+	let energy = shark_energy(&[
+		(6.0, Some(Element::Hydro), false),
+		(3.5, Some(Element::Pyro), false),
+		(3.0, Some(Element::Dendro), false),
+	]);
+	builtin(&characters::SHARK, config, Timeline::new(&steps, buffs, windows, hits)?, 20.0, energy)
+}
+
+// Nobody brings pyro, so the bites don't vaporize
+pub fn shark_furina_sige_kazuha(config: &Config) -> Result<Builtin, String> {
+	let buffs = vec![
+		buffs::hydro_resonance(&[Element::Hydro, Element::Hydro, Element::Hydro, Element::Anemo]).boxed(),
+		buffs::scroll(&[Element::Pyro, Element::Hydro], false).boxed(),	// Sige is on scroll
+	].into_iter()
+		.chain(set(config, vec![buffs::obsidian().boxed()]))
+		.chain(characters::SHARK.buffs(config))
+		.collect();
+	let windows = vec![
+		window(buffs::furina_burst(200.0), 1.0, 18.0),
+		window(buffs::kazuha_e(Element::Hydro), 4.0, 8.0),
+		window(buffs::vv_shred(Element::Hydro), 4.0, 10.0),
+	];
+
+	let (steps, hits) = shark_vape(config);
+	let hits = react(Element::Hydro, Vec::new(), hits);
+	// The salon goes off while Mualani is on field
+	let energy = shark_energy(&[
+		(6.0, Some(Element::Hydro), false),
+		(4.0, Some(Element::Hydro), true),
+		(4.0, Some(Element::Anemo), true),
+	]);
+	builtin(&characters::SHARK, config, Timeline::new(&steps, buffs, windows, hits)?, 20.0, energy)
+}
+
+pub fn shark_yelan_xl_zhong(config: &Config) -> Result<Builtin, String> {
+	let buffs = vec![
+		buffs::hydro_resonance(&[Element::Hydro, Element::Hydro, Element::Pyro, Element::Geo]).boxed(),
+		buffs::petra_share(Element::Hydro).boxed(),	// Zhong is on petra
+		buffs::instructor_share().boxed()		// Xiangling is on instructors
+	].into_iter()
+		.chain(set(config, vec![buffs::tenacity2pc().boxed(), buffs::hod2pc().boxed()]))
+		.chain(characters::SHARK.buffs(config))
+		.collect();
+	// Yelan's A4 ramps up over her burst, averaged like the other buffs
+	let windows = vec![window(buffs::yelan_a4(), 1.0, 15.0)];
+
+	// Pyronado comes around every 1.2s for 10s
+	let (steps, hits) = shark_vape(config);
+	let hits = react(Element::Hydro, gauge::repeated(Element::Pyro, 1.0, 2.0, 1.2, 12.0), hits);
+	// The salon goes off while Mualani is on field
+	let energy = shark_energy(&[
+		(4.0, Some(Element::Hydro), true),
+		(4.0, Some(Element::Pyro), false),
+		(0.5, Some(Element::Geo), true),
+	]);
+	builtin(&characters::SHARK, config, Timeline::new(&steps, buffs, windows, hits)?, 20.0, energy)
 }
 
 /////////////////////////////
@@ -225,79 +334,67 @@ pub fn shark_yelan_xl_zhong(
 
 fn v1_fraud_e_cast(
 	fraud: &CharStats,
-	vape: bool
+	config: &Config,
+	reaction: Reaction,
+	enemy: &Enemy
 ) -> f32 {
-	let cast_multiplier = 133.9 / 100.0;
-	let vape_multiplier = match vape {
-		true => forward_vape_multiplier(&fraud),
-		false => 1.0
-	};
+	let cast_multiplier = characters::FRAUD.multiplier("skill_cast", config) / 100.0;
 
 	damage(
+		fraud,
+		Element::Pyro,
 		fraud.atk * cast_multiplier,
-		1.0,
 		0.0,
-		(fraud.dmg_bonus + fraud.skill_bonus) / 100.0,
-		fraud.crit_rate,
-		fraud.crit_damage,
-		fraud.res_shred / 100.0,
-		vape_multiplier
+		fraud.skill_bonus,
+		reaction,
+		enemy
 	)
 }
 
 fn v1_fraud_e_tap(
 	fraud: &CharStats,
-	vape: bool
+	config: &Config,
+	reaction: Reaction,
+	enemy: &Enemy
 ) -> f32 {
-	let tap_multiplier = 230.4 / 100.0;
-	let vape_multiplier = match vape {
-		true => forward_vape_multiplier(&fraud),
-		false => 1.0
-	};
+	let tap_multiplier = characters::FRAUD.multiplier("skill_tap", config) / 100.0;
 
 	damage(
+		fraud,
+		Element::Pyro,
 		fraud.atk * tap_multiplier,
-		1.0,
 		0.0,
-		(fraud.dmg_bonus + fraud.skill_bonus) / 100.0,
-		fraud.crit_rate,
-		fraud.crit_damage,
-		fraud.res_shred / 100.0,
-		vape_multiplier
+		fraud.skill_bonus,
+		reaction,
+		enemy
 	)
 }
 
-pub fn fraud_yelan_furina_xilonen(
-	mainstats: &[f32; 6],
-	substats: &[usize; 5],
-	base: impl Fn(CharStats) -> CharStats,
-	buff: impl Fn(CharStats, CharStats) -> CharStats
-) -> f32 {
-	let stats = stats(
-		characters::FRAUD,
-		&base,								// This is the weapon base stat function
-		vec![								// This is a list of all the dynamic buffs
-			&buff,
-			&buffs::obsidian,
-			&buffs::furina_burst(100.0),
-			&buffs::yelan_a4,
-			&buffs::xilonen_shred,
-			&buffs::hydro_resonance,
-			&|base, mut stats| { stats.atk += 0.35 * base.atk; stats }, // Burst ascension passive
-		],
-		mainstats,
-		substats
-	);
+// Yelan, Furina then Xilonen before Mavuika casts at 4.5s and taps once a
+// second until 11s
+pub fn fraud_yelan_furina_xilonen(config: &Config) -> Result<Builtin, String> {
+	let buffs = vec![
+		buffs::xilonen_shred(&[Element::Pyro, Element::Hydro]).boxed(),
+		buffs::hydro_resonance(&[Element::Pyro, Element::Hydro, Element::Hydro, Element::Geo]).boxed(),
+		buffs::named("Ascension Passive", Source::Character, |base, mut stats| { stats.atk += 0.35 * base.atk; stats }).boxed(),
+	].into_iter()
+		.chain(set(config, vec![buffs::obsidian().boxed()]))
+		.chain(characters::FRAUD.buffs(config))
+		.collect();
+	let windows = vec![
+		window(buffs::yelan_a4(), 1.0, 15.0),
+		window(buffs::furina_burst(100.0), 2.0, 18.0),
+	];
 
 	// fraud tap > xilo eq > furina eq > yelan eq > do nothing
-	let mut dmg = 0.0;
-	dmg += v1_fraud_e_cast(&stats, true);
-	dmg += v1_fraud_e_tap(&stats, true);
-	dmg += v1_fraud_e_tap(&stats, true);
-	dmg += v1_fraud_e_tap(&stats, true);
-	dmg += v1_fraud_e_tap(&stats, true);
-	dmg += v1_fraud_e_tap(&stats, true);
-	dmg += v1_fraud_e_tap(&stats, true);
-	dmg += v1_fraud_e_tap(&stats, true);
-	dmg
-}
\ No newline at end of file
+	let config = *config;
+	let steps = vec![step("skill_cast", 4.5, 0.5, 15.0), step("skill_tap", 5.0, 7.0, 0.0)];
+	let mut hits = vec![hit(4.5, Some("skill"), move |stats, reaction, enemy| v1_fraud_e_cast(stats, &config, reaction, enemy))];
+	for i in 0..7 {
+		hits.push(hit(5.0 + i as f32, Some("skill"), move |stats, reaction, enemy| v1_fraud_e_tap(stats, &config, reaction, enemy)));
+	}
+	// Furina's salon members keep hydro up, the taps only vaporize off ICD
+	let hits = react(Element::Pyro, gauge::repeated(Element::Hydro, 1.0, 1.0, 2.5, 18.0), hits);
+
+	builtin(&characters::FRAUD, &config, Timeline::new(&steps, buffs, windows, hits)?, 20.0, None)
+}
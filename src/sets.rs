@@ -0,0 +1,155 @@
+use std::fmt;
+
+use crate::buffs::{self, BoxedBuff, Buff};
+use crate::weapons::Conditions;
+
+// Sets the build search can put on the character. Conditions work like
+// weapon passives', anything that isn't set falls back to the usual case.
+// Sets that are mostly there for the team (Instructor, VV, Scroll, Petra)
+// stay with the rotations' buffs.
+pub struct ArtifactSet {
+	pub id: &'static str,
+	pub name: &'static str,
+	pub two_piece: fn(&Conditions) -> Result<BoxedBuff, String>,
+	// The 2pc bonus included
+	pub four_piece: fn(&Conditions) -> Result<BoxedBuff, String>,
+}
+
+impl fmt::Debug for ArtifactSet {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{}", self.id)
+	}
+}
+
+pub const SETS: [ArtifactSet; 8] = [
+	ArtifactSet {
+		id: "obsidian",
+		name: "Obsidian Codex",
+		two_piece: |_| Ok(buffs::obsidian2pc().boxed()),
+		four_piece: |_| Ok(buffs::obsidian().boxed()),
+	},
+	ArtifactSet {
+		id: "hod",
+		name: "Heart of Depth",
+		two_piece: |_| Ok(buffs::hod2pc().boxed()),
+		four_piece: |_| Ok(buffs::hod().boxed()),
+	},
+	ArtifactSet {
+		id: "mh",
+		name: "Marechaussee Hunter",
+		two_piece: |_| Ok(buffs::mh2pc().boxed()),
+		four_piece: |_| Ok(buffs::mh().boxed()),
+	},
+	ArtifactSet {
+		id: "shime",
+		name: "Shimenawa's Reminiscence",
+		two_piece: |_| Ok(buffs::shime2pc().boxed()),
+		four_piece: |_| Ok(buffs::shime().boxed()),
+	},
+	ArtifactSet {
+		id: "gilded",
+		name: "Gilded Dreams",
+		two_piece: |_| Ok(buffs::gilded2pc().boxed()),
+		four_piece: |c| {
+			// Counts the other three party members, like Floating Dreams
+			let (same, other) = (c.stacks("same_type", 1, 3)?, c.stacks("other_type", 2, 3)?);
+			match same + other {
+				0..=3 => Ok(buffs::gilded(same, other).boxed()),
+				x => Err(format!("{} party members is more than the other three", x))
+			}
+		},
+	},
+	ArtifactSet {
+		id: "troupe",
+		name: "Gilded Troupe",
+		two_piece: |_| Ok(buffs::troupe2pc().boxed()),
+		four_piece: |c| Ok(buffs::troupe(c.flag("is_off_field", false)?).boxed()),
+	},
+	ArtifactSet {
+		id: "reverie",
+		name: "Crimson Reverie",
+		two_piece: |_| Ok(buffs::reverie2pc().boxed()),
+		four_piece: |c| Ok(buffs::reverie(c.stacks("stacks", 5, 5)?).boxed()),
+	},
+	ArtifactSet {
+		id: "tenacity",
+		name: "Tenacity of the Millelith",
+		two_piece: |_| Ok(buffs::tenacity2pc().boxed()),
+		four_piece: |_| Ok(buffs::tenacity().boxed()),
+	},
+];
+
+pub fn by_id(id: &str) -> Option<&'static ArtifactSet> {
+	SETS.iter().find(|x| x.id == id)
+}
+
+// What the character wears, a full set or two halves
+#[derive(Copy, Clone, Debug)]
+pub enum SetChoice {
+	Four(&'static ArtifactSet),
+	TwoTwo(&'static ArtifactSet, &'static ArtifactSet),
+}
+
+impl SetChoice {
+	// Every 4pc set, then every 2+2 combination
+	pub fn all() -> Vec<SetChoice> {
+		let mut choices: Vec<SetChoice> = SETS.iter().map(SetChoice::Four).collect();
+		for (i, a) in SETS.iter().enumerate() {
+			for b in &SETS[i + 1..] {
+				choices.push(SetChoice::TwoTwo(a, b));
+			}
+		}
+		choices
+	}
+
+	pub fn buffs(&self, conditions: &Conditions) -> Result<Vec<BoxedBuff>, String> {
+		let on = |set: &ArtifactSet, e: String| format!("'{}': {}", set.id, e);
+		match self {
+			SetChoice::Four(set) => Ok(vec![(set.four_piece)(conditions).map_err(|e| on(set, e))?]),
+			SetChoice::TwoTwo(a, b) => Ok(vec![
+				(a.two_piece)(conditions).map_err(|e| on(a, e))?,
+				(b.two_piece)(conditions).map_err(|e| on(b, e))?,
+			]),
+		}
+	}
+}
+
+impl PartialEq for SetChoice {
+	fn eq(&self, other: &Self) -> bool {
+		match (self, other) {
+			(SetChoice::Four(a), SetChoice::Four(b)) => a.id == b.id,
+			(SetChoice::TwoTwo(a, b), SetChoice::TwoTwo(c, d)) => a.id == c.id && b.id == d.id,
+			_ => false
+		}
+	}
+}
+
+impl fmt::Display for SetChoice {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			SetChoice::Four(set) => write!(f, "{} 4pc", set.name),
+			SetChoice::TwoTwo(a, b) => write!(f, "{} 2pc + {} 2pc", a.name, b.name),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn every_4pc_and_2_plus_2() {
+		let all = SetChoice::all();
+		assert_eq!(all.len(), SETS.len() + SETS.len() * (SETS.len() - 1) / 2);
+		assert_eq!(all[0].to_string(), "Obsidian Codex 4pc");
+		assert_eq!(all[SETS.len()].to_string(), "Obsidian Codex 2pc + Heart of Depth 2pc");
+
+		// A set's 2pc and 4pc never end up on the same character
+		for choice in all {
+			assert!(buffs::validate(&choice.buffs(&Conditions::default()).unwrap()).is_ok());
+		}
+		let mut same = SetChoice::Four(by_id("hod").unwrap()).buffs(&Conditions::default()).unwrap();
+		same.extend(SetChoice::TwoTwo(by_id("hod").unwrap(), by_id("mh").unwrap()).buffs(&Conditions::default()).unwrap());
+		assert!(buffs::validate(&same).is_err());
+	}
+}
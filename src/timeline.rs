@@ -0,0 +1,204 @@
+use std::collections::HashMap;
+
+use crate::*;
+use crate::artifacts::ArtifactBuild;
+use crate::buffs::{self, ActiveBuff, BoxedBuff, Buff};
+use crate::enemies::Enemy;
+use crate::rolls::RollPolicy;
+
+// Something the character does. It keeps them busy for `duration` and can't
+// be used again until `cooldown` after it started.
+pub struct Step {
+	pub name: String,
+	pub start: f32,
+	pub duration: f32,
+	pub cooldown: f32,
+}
+
+// A buff that's only up for part of the rotation, i.e. Bennett's burst field
+pub struct Window {
+	pub buff: BoxedBuff,
+	pub start: f32,
+	pub duration: f32,
+}
+
+impl Window {
+	pub fn covers(&self, time: f32) -> bool {
+		time >= self.start && time < self.start + self.duration
+	}
+}
+
+pub type HitDamage = Box<dyn Fn(&CharStats, &Enemy) -> f32 + Send + Sync>;
+
+pub struct TimedHit {
+	pub time: f32,
+	pub damage: HitDamage,
+}
+
+// Checks that steps don't overlap and respect their cooldowns, and returns
+// when the last one ends
+pub fn check_steps(steps: &[Step]) -> Result<f32, String> {
+	let mut sorted: Vec<&Step> = steps.iter().collect();
+	sorted.sort_by(|a, b| a.start.total_cmp(&b.start));
+
+	for (i, step) in sorted.iter().enumerate() {
+		if step.start < 0.0 || step.duration < 0.0 || step.cooldown < 0.0 {
+			return Err(format!("'{}' at {}s: times can't be negative", step.name, step.start));
+		}
+		if let Some(previous) = i.checked_sub(1).map(|x| sorted[x]) {
+			if step.start < previous.start + previous.duration {
+				return Err(format!(
+					"'{}' at {}s starts before '{}' ends at {}s",
+					step.name, step.start, previous.name, previous.start + previous.duration
+				));
+			}
+		}
+		if let Some(previous) = sorted[..i].iter().rev().find(|x| x.name == step.name) {
+			if step.start < previous.start + previous.cooldown {
+				return Err(format!(
+					"'{}' at {}s is still on cooldown until {}s",
+					step.name, step.start, previous.start + previous.cooldown
+				));
+			}
+		}
+	}
+	Ok(sorted.iter().map(|x| x.start + x.duration).fold(0.0, f32::max))
+}
+
+// Hits at fixed times, each one computed with the buffs that are always up
+// plus the windows it falls into. Untimed rotations are all hits at 0s.
+pub struct Timeline {
+	buffs: Vec<BoxedBuff>,
+	windows: Vec<Window>,
+	hits: Vec<TimedHit>,
+	// Hits grouped by the windows covering them, so stats are computed once per group
+	groups: Vec<(Vec<usize>, Vec<usize>)>,
+	length: f32,
+}
+
+impl Timeline {
+	pub fn new(steps: &[Step], buffs: Vec<BoxedBuff>, windows: Vec<Window>, hits: Vec<TimedHit>) -> Result<Self, String> {
+		let length = hits.iter().map(|x| x.time).fold(check_steps(steps)?, f32::max);
+
+		let mut groups: Vec<(Vec<usize>, Vec<usize>)> = Vec::new();
+		for (i, hit) in hits.iter().enumerate() {
+			let covering: Vec<usize> = (0..windows.len()).filter(|x| windows[*x].covers(hit.time)).collect();
+			match groups.iter_mut().find(|x| x.0 == covering) {
+				Some(group) => group.1.push(i),
+				None => groups.push((covering, vec![i])),
+			}
+		}
+
+		// Two of the same window are fine, as long as they don't overlap on a hit
+		for (covering, group) in &groups {
+			let active: Vec<Box<dyn Buff + '_>> = buffs.iter()
+				.map(|x| x.boxed())
+				.chain(covering.iter().map(|x| (&windows[*x].buff).boxed()))
+				.collect();
+			buffs::validate(&active).map_err(|e| format!("at {}s: {}", hits[group[0]].time, e))?;
+		}
+
+		Ok(Timeline { buffs, windows, hits, groups, length })
+	}
+
+	pub fn length(&self) -> f32 {
+		self.length
+	}
+
+	// Same shape as the other rotations. The stats are the ones of the biggest
+	// hit, and window uptimes are the share of hits they covered.
+	#[allow(clippy::too_many_arguments)]
+	pub fn run(
+		&self,
+		base_stats: CharStats,
+		weapon_base: &dyn Fn(CharStats) -> CharStats,
+		weapon_buff: &BoxedBuff,
+		build: &ArtifactBuild,
+		substats: &Substats,
+		policy: &RollPolicy,
+		enemy: &Enemy
+	) -> (f32, CharStats, Vec<ActiveBuff>) {
+		let mut total = 0.0;
+		let mut biggest = (f32::MIN, None);
+		let mut active = Vec::new();
+		let mut covered: HashMap<&str, usize> = HashMap::new();
+
+		for (covering, group) in &self.groups {
+			let mut dynamic_buffs = vec![weapon_buff.boxed()];
+			dynamic_buffs.extend(self.buffs.iter().map(|x| x.boxed()));
+			dynamic_buffs.extend(covering.iter().map(|x| (&self.windows[*x].buff).boxed()));
+			let (stats, group_active) = stats(base_stats, weapon_base, dynamic_buffs, build, substats, policy);
+
+			for &hit in group {
+				let damage = (self.hits[hit].damage)(&stats, enemy);
+				total += damage;
+				if damage > biggest.0 {
+					biggest = (damage, Some(stats));
+				}
+			}
+			// The weapon and the buffs that are always up come first
+			if active.is_empty() {
+				active = group_active.into_iter().take(1 + self.buffs.len()).collect();
+			}
+			let mut names: Vec<&str> = covering.iter().map(|x| self.windows[*x].buff.name()).collect();
+			names.dedup();
+			for name in names {
+				*covered.entry(name).or_default() += group.len();
+			}
+		}
+
+		// Windows in the order they were given, once per buff
+		let mut seen = Vec::new();
+		for window in &self.windows {
+			let name = window.buff.name();
+			if seen.contains(&name) {
+				continue;
+			}
+			seen.push(name);
+			let mut buff = ActiveBuff::of(window.buff.as_ref());
+			buff.uptime *= covered.get(name).copied().unwrap_or(0) as f32 / self.hits.len() as f32;
+			active.push(buff);
+		}
+
+		let stats = biggest.1.unwrap_or_else(|| stats(base_stats, weapon_base, vec![weapon_buff.boxed()], build, substats, policy).0);
+		(total, stats, active)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::artifacts::ArtifactStat;
+	use crate::buffs::Source;
+
+	fn step(name: &str, start: f32, duration: f32, cooldown: f32) -> Step {
+		Step { name: name.to_string(), start, duration, cooldown }
+	}
+
+	#[test]
+	fn checks_overlaps_and_cooldowns() {
+		assert_eq!(check_steps(&[step("skill", 0.0, 1.0, 5.0), step("burst", 1.0, 2.0, 15.0), step("skill", 5.0, 1.0, 5.0)]), Ok(6.0));
+		assert!(check_steps(&[step("skill", 0.0, 1.0, 5.0), step("burst", 0.5, 2.0, 15.0)]).unwrap_err().contains("starts before"));
+		assert!(check_steps(&[step("skill", 0.0, 1.0, 5.0), step("skill", 4.0, 1.0, 5.0)]).unwrap_err().contains("cooldown"));
+	}
+
+	#[test]
+	fn windows_only_buff_the_hits_inside() {
+		let hit = |time| TimedHit { time, damage: Box::new(|stats: &CharStats, _: &Enemy| stats.atk) };
+		let window = Window {
+			buff: buffs::named("Flat ATK", Source::Character, |_, mut stats| { stats.atk += 100.0; stats }).boxed(),
+			start: 1.0,
+			duration: 2.0,
+		};
+		let timeline = Timeline::new(&[], Vec::new(), vec![window], vec![hit(0.0), hit(1.0), hit(2.5), hit(3.0)]).unwrap();
+
+		let base = characters::FRAUD.base(characters::Level::MAX).unwrap();
+		let weapon = buffs::named("Nothing", Source::Weapon, |_, stats| stats).boxed();
+		let build = ArtifactBuild::layout(ArtifactStat::Em, ArtifactStat::Em, ArtifactStat::Em).unwrap();
+		let (dmg, _, active) = timeline.run(base, &|x| x, &weapon, &build, &[0; 7], &Default::default(), &enemies::by_name("masanori").unwrap());
+		let unbuffed = stats(base, |x| x, vec![weapon.boxed()], &build, &[0; 7], &Default::default()).0.atk;
+		assert!((dmg - (4.0 * unbuffed + 200.0)).abs() < 1e-2);
+		assert_eq!(timeline.length(), 3.0);
+		assert_eq!(active.last().unwrap().uptime, 0.5);
+	}
+}
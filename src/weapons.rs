@@ -0,0 +1,368 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::CharStats;
+use crate::buffs::{self, Buff, BoxedBuff, MagicBoxed, Passive, Source};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum WeaponClass {
+	Sword,
+	Claymore,
+	Polearm,
+	Catalyst,
+	Bow,
+}
+
+impl WeaponClass {
+	pub const ALL: [WeaponClass; 5] = [
+		WeaponClass::Sword,
+		WeaponClass::Claymore,
+		WeaponClass::Polearm,
+		WeaponClass::Catalyst,
+		WeaponClass::Bow,
+	];
+
+	pub fn name(&self) -> &'static str {
+		match self {
+			WeaponClass::Sword => "sword",
+			WeaponClass::Claymore => "claymore",
+			WeaponClass::Polearm => "polearm",
+			WeaponClass::Catalyst => "catalyst",
+			WeaponClass::Bow => "bow",
+		}
+	}
+}
+
+impl FromStr for WeaponClass {
+	type Err = String;
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s {
+			"sword" => Ok(WeaponClass::Sword),
+			"claymore" => Ok(WeaponClass::Claymore),
+			"polearm" | "spear" => Ok(WeaponClass::Polearm),
+			"catalyst" => Ok(WeaponClass::Catalyst),
+			"bow" => Ok(WeaponClass::Bow),
+			_ => Err(format!("unknown weapon class '{}', expected sword, claymore, polearm, catalyst or bow", s))
+		}
+	}
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum WeaponStat {
+	HpPercent,
+	AtkPercent,
+	DefPercent,
+	Em,
+	CritRate,
+	CritDamage,
+	EnergyRecharge,
+}
+
+impl fmt::Display for WeaponStat {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		f.write_str(match self {
+			WeaponStat::HpPercent => "HP%",
+			WeaponStat::AtkPercent => "ATK%",
+			WeaponStat::DefPercent => "DEF%",
+			WeaponStat::Em => "EM",
+			WeaponStat::CritRate => "CR",
+			WeaponStat::CritDamage => "CD",
+			WeaponStat::EnergyRecharge => "ER%",
+		})
+	}
+}
+
+// What the passive depends on, i.e. `stacks = 4` or `buff = false` (0.0).
+// Anything that isn't set falls back to what we usually assume for the weapon,
+// anything that is set has to be something the passive can actually be at.
+#[derive(Clone, Debug, Default)]
+pub struct Conditions(HashMap<String, f32>);
+
+impl Conditions {
+	pub fn set(&mut self, key: &str, value: f32) {
+		self.0.insert(key.to_string(), value);
+	}
+
+	pub fn stacks(&self, key: &str, default: usize, max: usize) -> Result<usize, String> {
+		match self.0.get(key) {
+			None => Ok(default),
+			Some(x) if *x >= 0.0 && *x <= max as f32 && x.fract() == 0.0 => Ok(*x as usize),
+			Some(x) => Err(format!("'{}' should be a whole number within 0-{}, got {}", key, max, x))
+		}
+	}
+
+	pub fn flag(&self, key: &str, default: bool) -> Result<bool, String> {
+		match self.0.get(key) {
+			None => Ok(default),
+			Some(x) if *x == 0.0 || *x == 1.0 => Ok(*x == 1.0),
+			Some(x) => Err(format!("'{}' should be true or false, got {}", key, x))
+		}
+	}
+}
+
+// Base ATK and substat are plain data so they can't drift from the passive,
+// the passive itself is built for a given refinement and set of conditions.
+pub struct Weapon {
+	pub id: &'static str,
+	pub name: &'static str,
+	pub class: WeaponClass,
+	pub rarity: usize,
+	pub base_atk: f32,
+	pub substat: (WeaponStat, f32),
+	pub passive: fn(usize, &Conditions) -> Result<Passive, String>,
+}
+
+impl Weapon {
+	// Flat stats, percentage substats scale off of the base and go into `buff`
+	pub fn base(&self, mut stats: CharStats) -> CharStats {
+		stats.atk += self.base_atk;
+		let (stat, value) = self.substat;
+		match stat {
+			WeaponStat::Em => stats.em += value,
+			WeaponStat::CritRate => stats.crit_rate += value,
+			WeaponStat::CritDamage => stats.crit_damage += value,
+			WeaponStat::EnergyRecharge => stats.energy_recharge += value,
+			WeaponStat::HpPercent | WeaponStat::AtkPercent | WeaponStat::DefPercent => ()
+		}
+		stats
+	}
+
+	pub fn buff(&'static self, refinement: usize, conditions: &Conditions) -> Result<BoxedBuff, String> {
+		Ok(WeaponBuff {
+			weapon: self,
+			passive: (self.passive)(refinement, conditions)?,
+		}.boxed())
+	}
+
+	pub fn at(&'static self, refinement: usize, conditions: &Conditions) -> Result<WeaponEntry, String> {
+		if !(1..=5).contains(&refinement) {
+			return Err(format!("'{}': refinement {} is not within 1-5", self.id, refinement));
+		}
+		Ok(WeaponEntry {
+			name: format!("{} R{}", self.name, refinement),
+			weapon: self,
+			buff: self.buff(refinement, conditions).map_err(|e| format!("'{}': {}", self.id, e))?,
+		})
+	}
+}
+
+// Percentage substat and passive, named after the weapon
+struct WeaponBuff {
+	weapon: &'static Weapon,
+	passive: Passive,
+}
+
+impl Buff for WeaponBuff {
+	fn name(&self) -> &str {
+		self.weapon.name
+	}
+
+	fn source(&self) -> Source {
+		Source::Weapon
+	}
+
+	fn apply(&self, base: CharStats, mut stats: CharStats) -> CharStats {
+		let (stat, value) = self.weapon.substat;
+		match stat {
+			WeaponStat::HpPercent => stats.hp += base.hp * value / 100.0,
+			WeaponStat::AtkPercent => stats.atk += base.atk * value / 100.0,
+			WeaponStat::DefPercent => stats.def += base.def * value / 100.0,
+			_ => ()
+		}
+		(self.passive.effect)(base, stats)
+	}
+
+	fn convert(&self, base: CharStats, pre: CharStats, stats: CharStats) -> CharStats {
+		match &self.passive.conversion {
+			Some(conversion) => conversion(base, pre, stats),
+			None => stats
+		}
+	}
+}
+
+// A weapon at a specific refinement, what `setup_calculator` ranks
+pub struct WeaponEntry {
+	pub name: String,
+	pub weapon: &'static Weapon,
+	pub buff: BoxedBuff,
+}
+
+/////////////////////////////
+//////// Registry ///////////
+/////////////////////////////
+
+pub fn by_id(id: &str) -> Option<&'static Weapon> {
+	WEAPONS.iter().find(|x| x.id == id)
+}
+
+// "Sun R1" and friends, as printed in the reports
+pub fn by_display_name(name: &str) -> Option<WeaponEntry> {
+	WEAPONS.iter()
+		.flat_map(|x| (1..=5).map(move |r| (x, r)))
+		.find(|(x, r)| format!("{} R{}", x.name, r) == name)
+		.map(|(x, r)| x.at(r, &Conditions::default()).unwrap())
+}
+
+pub fn of_class(class: WeaponClass) -> impl Iterator<Item = &'static Weapon> {
+	WEAPONS.iter().filter(move |x| x.class == class)
+}
+
+// Every weapon of the class at each of the refinements, i.e. all catalysts at R1 and R5
+pub fn list(class: WeaponClass, refinements: &[usize], conditions: &Conditions) -> Result<Vec<WeaponEntry>, String> {
+	let mut entries = Vec::new();
+	for weapon in of_class(class) {
+		for &refinement in refinements {
+			entries.push(weapon.at(refinement, conditions)?);
+		}
+	}
+	Ok(entries)
+}
+
+pub static WEAPONS: [Weapon; 25] = [
+	// Claymores
+	Weapon {
+		id: "earth_shaker", name: "Earth Shaker", class: WeaponClass::Claymore, rarity: 4,
+		base_atk: 565.0, substat: (WeaponStat::AtkPercent, 27.6),
+		passive: |r, _| Ok(buffs::earth_shaker_buff(r).boxed()),
+	},
+	Weapon {
+		id: "tidal_shadow", name: "Tidal Shadow", class: WeaponClass::Claymore, rarity: 4,
+		base_atk: 510.0, substat: (WeaponStat::AtkPercent, 41.3),
+		passive: |r, c| Ok(buffs::tidal_shadow_buff(r, c.flag("healed", true)?).boxed()),
+	},
+	Weapon {
+		id: "mailed_flower", name: "Mailed Flower", class: WeaponClass::Claymore, rarity: 4,
+		base_atk: 565.0, substat: (WeaponStat::Em, 110.0),
+		passive: |r, c| Ok(buffs::mailed_flower_buff(r, c.flag("buff", true)?).boxed()),
+	},
+	Weapon {
+		id: "serpent_spine", name: "Serpent Spine", class: WeaponClass::Claymore, rarity: 4,
+		base_atk: 510.0, substat: (WeaponStat::CritRate, 27.6),
+		passive: |r, c| Ok(buffs::serpent_spine_buff(r, c.stacks("stacks", 5, 5)?).boxed()),
+	},
+	Weapon {
+		id: "rainslasher", name: "Rainslasher", class: WeaponClass::Claymore, rarity: 4,
+		base_atk: 510.0, substat: (WeaponStat::Em, 165.0),
+		passive: |r, c| Ok(buffs::rainslasher_buff(r, c.flag("buff", true)?).boxed()),
+	},
+	Weapon {
+		id: "sun", name: "Sun", class: WeaponClass::Claymore, rarity: 5,
+		base_atk: 741.0, substat: (WeaponStat::CritRate, 11.0),
+		passive: |r, c| Ok(buffs::sun_buff(r, c.flag("buff", true)?, c.flag("blessing", true)?).boxed()),
+	},
+	Weapon {
+		id: "wgs", name: "Wolf's Gravestone", class: WeaponClass::Claymore, rarity: 5,
+		base_atk: 608.0, substat: (WeaponStat::AtkPercent, 49.6),
+		passive: |r, _| Ok(buffs::wgs_buff(r).boxed()),
+	},
+
+	// Catalysts
+	Weapon {
+		id: "surfing_time", name: "Surfing Time", class: WeaponClass::Catalyst, rarity: 5,
+		base_atk: 542.0, substat: (WeaponStat::CritDamage, 88.2),
+		passive: |r, c| Ok(buffs::surfing_time_buff(r, c.stacks("stacks", 4, 4)?).boxed()),
+	},
+	Weapon {
+		id: "sac_jade", name: "Sacrificial Jade", class: WeaponClass::Catalyst, rarity: 4,
+		base_atk: 454.0, substat: (WeaponStat::CritRate, 36.8),
+		passive: |r, _| Ok(buffs::sac_jade_buff(r).boxed()),
+	},
+	Weapon {
+		id: "ceiba", name: "Ring of Yaxche", class: WeaponClass::Catalyst, rarity: 4,
+		base_atk: 510.0, substat: (WeaponStat::HpPercent, 41.3),
+		passive: |r, _| Ok(buffs::ceiba_buff(r)),
+	},
+	Weapon {
+		id: "magic_guide", name: "Magic Guide", class: WeaponClass::Catalyst, rarity: 3,
+		base_atk: 354.0, substat: (WeaponStat::Em, 187.0),
+		passive: |r, c| Ok(buffs::magic_guide_buff(r, c.flag("hydro_aura", false)?).boxed()),
+	},
+	Weapon {
+		id: "solar_pearl", name: "Solar Pearl", class: WeaponClass::Catalyst, rarity: 4,
+		base_atk: 510.0, substat: (WeaponStat::CritRate, 27.6),
+		passive: |r, _| Ok(buffs::solar_pearl_buff(r).boxed()),
+	},
+	// The Widsith rolls one of its buffs at random, so each one gets an entry
+	Weapon {
+		id: "widsith_atk", name: "The Widsith [ATK]", class: WeaponClass::Catalyst, rarity: 4,
+		base_atk: 510.0, substat: (WeaponStat::CritDamage, 55.1),
+		passive: |r, _| Ok(buffs::widsith_buff(r, 0).boxed()),
+	},
+	Weapon {
+		id: "widsith_dmg", name: "The Widsith [DMG]", class: WeaponClass::Catalyst, rarity: 4,
+		base_atk: 510.0, substat: (WeaponStat::CritDamage, 55.1),
+		passive: |r, _| Ok(buffs::widsith_buff(r, 1).boxed()),
+	},
+	Weapon {
+		id: "widsith_em", name: "The Widsith [EM]", class: WeaponClass::Catalyst, rarity: 4,
+		base_atk: 510.0, substat: (WeaponStat::CritDamage, 55.1),
+		passive: |r, _| Ok(buffs::widsith_buff(r, 2).boxed()),
+	},
+	Weapon {
+		id: "widsith_none", name: "The Widsith [None]", class: WeaponClass::Catalyst, rarity: 4,
+		base_atk: 510.0, substat: (WeaponStat::CritDamage, 55.1),
+		passive: |r, _| Ok(buffs::widsith_buff(r, 3).boxed()),
+	},
+	Weapon {
+		id: "floating_dreams", name: "Floating Dreams", class: WeaponClass::Catalyst, rarity: 5,
+		base_atk: 542.0, substat: (WeaponStat::Em, 265.0),
+		passive: |r, c| {
+			// The other three party members, counted by whether they share the wielder's element
+			let (same, other) = (c.stacks("same_types_count", 0, 3)?, c.stacks("other_types_count", 3, 3)?);
+			match same + other {
+				0..=3 => Ok(buffs::floating_dreams_buff(r, same, other).boxed()),
+				x => Err(format!("{} party members is more than the other three", x))
+			}
+		},
+	},
+	Weapon {
+		id: "tome", name: "Tome of Eternal Flow", class: WeaponClass::Catalyst, rarity: 5,
+		base_atk: 542.0, substat: (WeaponStat::CritDamage, 88.2),
+		passive: |r, c| Ok(buffs::tome_buff(r, c.stacks("stacks", 0, 3)?).boxed()),
+	},
+	Weapon {
+		id: "tulaytullah", name: "Tulaytullah's Remembrance", class: WeaponClass::Catalyst, rarity: 5,
+		base_atk: 674.0, substat: (WeaponStat::CritDamage, 44.1),
+		passive: |r, c| {
+			// A hit stack is worth two time stacks and the bonus caps at ten time stacks
+			let (time, hit) = (c.stacks("stacks_time", 10, 10)?, c.stacks("stacks_hit", 0, 5)?);
+			match time + 2 * hit {
+				0..=10 => Ok(buffs::tulaytullah_buff(r, time, hit).boxed()),
+				_ => Err(format!("{} time and {} hit stacks are over the cap of 10 time stacks", time, hit))
+			}
+		},
+	},
+	Weapon {
+		id: "prayer", name: "Lost Prayer to the Sacred Winds", class: WeaponClass::Catalyst, rarity: 5,
+		base_atk: 608.0, substat: (WeaponStat::CritRate, 33.1),
+		passive: |r, c| Ok(buffs::prayer_buff(r, c.stacks("stacks", 4, 4)?).boxed()),
+	},
+
+	// Polearms
+	Weapon {
+		id: "lumidouce", name: "Lumidouce Elegy", class: WeaponClass::Polearm, rarity: 5,
+		base_atk: 608.0, substat: (WeaponStat::CritRate, 33.1),
+		passive: |r, c| Ok(buffs::lumidouce_buff(r, c.stacks("stacks", 2, 2)?).boxed()),
+	},
+	Weapon {
+		id: "homa", name: "Staff of Homa", class: WeaponClass::Polearm, rarity: 5,
+		base_atk: 608.0, substat: (WeaponStat::CritDamage, 66.4),
+		passive: |r, c| Ok(buffs::homa_buff(r, c.flag("under_half_hp", false)?)),
+	},
+	Weapon {
+		id: "soss", name: "Scarlet Sands", class: WeaponClass::Polearm, rarity: 5,
+		base_atk: 542.0, substat: (WeaponStat::CritRate, 44.1),
+		passive: |r, c| Ok(buffs::soss_buff(r, c.stacks("stacks", 1, 3)?)),
+	},
+	Weapon {
+		id: "dragons", name: "Dragon's Bane", class: WeaponClass::Polearm, rarity: 4,
+		base_atk: 454.0, substat: (WeaponStat::Em, 221.0),
+		passive: |r, _| Ok(buffs::dragons_buff(r).boxed()),
+	},
+	Weapon {
+		id: "missive", name: "Missive Windspear", class: WeaponClass::Polearm, rarity: 4,
+		base_atk: 510.0, substat: (WeaponStat::AtkPercent, 41.3),
+		passive: |r, _| Ok(buffs::missive_buff(r).boxed()),
+	},
+];